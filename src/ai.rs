@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+
+use chess::game::*;
+use chess::limits::*;
+use chess::moves::*;
+use chess::piece::*;
+use chess::position::*;
+use chess::search::*;
+
+use crate::board::*;
+
+/// The sides the computer plays, and how long it thinks for
+pub struct ComputerPlayer {
+    pub colors: Vec<PieceColor>,
+    pub limits: Limits,
+}
+
+impl ComputerPlayer {
+    pub fn plays(&self, color: PieceColor) -> bool {
+        self.colors.contains(&color)
+    }
+
+    /// Whether the computer plays the side against a person playing the other
+    pub fn plays_against(&self, color: PieceColor) -> bool {
+        self.plays(color) && !self.plays(color.opponent())
+    }
+}
+
+// Component for a search for the computer's move, running on another thread
+// so the board can still be drawn meanwhile
+struct Thinking {
+    /// The position searched from, which the move is only any good for
+    position: Position,
+    /// Set to give up on the search
+    stop: Arc<AtomicBool>,
+    task: Task<Option<Move>>,
+}
+
+/// Starts searching for a move once it is the computer's turn
+fn start_thinking(
+    mut commands: Commands,
+    computer: Res<ComputerPlayer>,
+    game: Res<Game>,
+    thread_pool: Res<AsyncComputeTaskPool>,
+    thinking_query: Query<&Thinking>,
+) {
+    let position = *game.position();
+    if game.is_over()
+        || !computer.plays(position.side_to_move())
+        || thinking_query.iter().next().is_some()
+    {
+        return;
+    }
+    let mut search = Search::new(&game);
+    let stop = search.stop_flag();
+    let limits = computer.limits;
+    let task = thread_pool.spawn(async move { search.search(&limits).map(|(mv, _score)| mv) });
+    commands.spawn().insert(Thinking {
+        position,
+        stop,
+        task,
+    });
+}
+
+/// Plays the move found, like a click on the board would, unless the game
+/// has moved on while the computer was thinking, for example after a move was
+/// taken back. The search is then stopped early, as its move is of no use.
+fn finish_thinking(
+    mut commands: Commands,
+    game: Res<Game>,
+    mut move_requests: EventWriter<MoveRequest>,
+    mut thinking_query: Query<(Entity, &mut Thinking)>,
+) {
+    for (entity, mut thinking) in thinking_query.iter_mut() {
+        if thinking.position != *game.position() {
+            thinking.stop.store(true, Ordering::Relaxed);
+        }
+        if let Some(best_move) = future::block_on(future::poll_once(&mut thinking.task)) {
+            commands.entity(entity).despawn();
+            if let Some(mv) = best_move.filter(|_| thinking.position == *game.position()) {
+                move_requests.send(mv.into());
+            }
+        }
+    }
+}
+
+pub struct ComputerPlayerPlugin;
+
+impl Plugin for ComputerPlayerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // The move found is requested before the board handles this frame's
+        // requests, and a new search starts once they have been made
+        app.add_system_to_stage(CoreStage::PreUpdate, finish_thinking.system())
+            .add_system_to_stage(CoreStage::PostUpdate, start_thinking.system());
+    }
+}
@@ -0,0 +1,231 @@
+//! The squares each kind of piece attacks, looked up from tables computed at
+//! compile time. Sliding pieces use the classical approach: a ray in each
+//! direction, cut short at the first piece standing on it. A piece attacks
+//! along every movement its kind combines.
+//!
+//! The tables are for the widest board. Rays and leaps never wrap around from
+//! one edge to the other, so a narrower board only has to leave out the
+//! squares past its last file.
+
+use crate::bitboard::*;
+use crate::piece::*;
+use crate::square::*;
+
+const fn on_board(x: i8, y: i8) -> bool {
+    x >= 0 && x < MAX_FILES as i8 && y >= 0 && y < RANKS as i8
+}
+
+/// The set of squares at the given offsets from each square, leaving out the
+/// ones that fall off the board
+const fn offset_table(offsets: &[(i8, i8)]) -> [u128; SQUARES] {
+    let mut table = [0; SQUARES];
+    let mut index = 0;
+    while index < SQUARES {
+        let x = (index % MAX_FILES as usize) as i8;
+        let y = (index / MAX_FILES as usize) as i8;
+        let mut i = 0;
+        while i < offsets.len() {
+            let (dx, dy) = offsets[i];
+            if on_board(x + dx, y + dy) {
+                table[index] |= 1 << ((y + dy) * MAX_FILES as i8 + x + dx);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+/// The squares from each square to the edge of the board in one direction
+const fn ray_table(dx: i8, dy: i8) -> [u128; SQUARES] {
+    let mut table = [0; SQUARES];
+    let mut index = 0;
+    while index < SQUARES {
+        let mut x = (index % MAX_FILES as usize) as i8 + dx;
+        let mut y = (index / MAX_FILES as usize) as i8 + dy;
+        while on_board(x, y) {
+            table[index] |= 1 << (y * MAX_FILES as i8 + x);
+            x += dx;
+            y += dy;
+        }
+        index += 1;
+    }
+    table
+}
+
+const KNIGHT_ATTACKS: [u128; SQUARES] = offset_table(&[
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+]);
+
+const KING_ATTACKS: [u128; SQUARES] = offset_table(&[
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+]);
+
+// White pawns advance towards y = 0, black pawns towards y = 7
+const WHITE_PAWN_ATTACKS: [u128; SQUARES] = offset_table(&[(-1, -1), (1, -1)]);
+const BLACK_PAWN_ATTACKS: [u128; SQUARES] = offset_table(&[(-1, 1), (1, 1)]);
+
+/// Rays in the directions that increase the square index, where the nearest
+/// piece on a ray is its lowest set bit
+const POSITIVE_RAYS: [[u128; SQUARES]; 4] = [
+    ray_table(1, 0),
+    ray_table(0, 1),
+    ray_table(1, 1),
+    ray_table(-1, 1),
+];
+
+/// Rays in the directions that decrease the square index, where the nearest
+/// piece on a ray is its highest set bit
+const NEGATIVE_RAYS: [[u128; SQUARES]; 4] = [
+    ray_table(-1, 0),
+    ray_table(0, -1),
+    ray_table(-1, -1),
+    ray_table(1, -1),
+];
+
+// Indices into the ray tables
+const ORTHOGONAL: [usize; 2] = [0, 1];
+const DIAGONAL: [usize; 2] = [2, 3];
+
+fn positive_ray_attacks(direction: usize, square: Square, occupied: Bitboard) -> u128 {
+    let ray = POSITIVE_RAYS[direction][square.index()];
+    let blockers = ray & occupied.0;
+    if blockers == 0 {
+        return ray;
+    }
+    ray ^ POSITIVE_RAYS[direction][blockers.trailing_zeros() as usize]
+}
+
+fn negative_ray_attacks(direction: usize, square: Square, occupied: Bitboard) -> u128 {
+    let ray = NEGATIVE_RAYS[direction][square.index()];
+    let blockers = ray & occupied.0;
+    if blockers == 0 {
+        return ray;
+    }
+    ray ^ NEGATIVE_RAYS[direction][SQUARES - 1 - blockers.leading_zeros() as usize]
+}
+
+fn slider_attacks(directions: [usize; 2], square: Square, occupied: Bitboard) -> Bitboard {
+    let mut attacks = 0;
+    for direction in directions {
+        attacks |= positive_ray_attacks(direction, square, occupied);
+        attacks |= negative_ray_attacks(direction, square, occupied);
+    }
+    Bitboard(attacks)
+}
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    Bitboard(KNIGHT_ATTACKS[square.index()])
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    Bitboard(KING_ATTACKS[square.index()])
+}
+
+/// The squares a pawn of the color captures on from the square
+pub fn pawn_attacks(color: PieceColor, square: Square) -> Bitboard {
+    match color {
+        PieceColor::White => Bitboard(WHITE_PAWN_ATTACKS[square.index()]),
+        PieceColor::Black => Bitboard(BLACK_PAWN_ATTACKS[square.index()]),
+    }
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    slider_attacks(ORTHOGONAL, square, occupied)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    slider_attacks(DIAGONAL, square, occupied)
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+/// The squares attacked from the square by moving the one way, given the
+/// occupied squares that block sliding. Pawn captures go the way pawns of the
+/// color advance.
+pub fn movement_attacks(
+    movement: Movement,
+    color: PieceColor,
+    square: Square,
+    occupied: Bitboard,
+) -> Bitboard {
+    match movement {
+        Movement::Orthogonal => rook_attacks(square, occupied),
+        Movement::Diagonal => bishop_attacks(square, occupied),
+        Movement::KnightLeap => knight_attacks(square),
+        Movement::KingStep => king_attacks(square),
+        Movement::PawnCapture => pawn_attacks(color, square),
+    }
+}
+
+/// The squares a piece attacks from the square, given the occupied squares
+/// that block sliding pieces
+pub fn piece_attacks(piece: Piece, occupied: Bitboard) -> Bitboard {
+    let mut attacks = Bitboard::EMPTY;
+    for movement in piece.kind.movements() {
+        attacks |= movement_attacks(*movement, piece.color, piece.square, occupied);
+    }
+    attacks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::attacks::*;
+
+    #[test]
+    fn test_slider_attacks() {
+        let rook = Square { x: 0, y: 7 };
+        let blocker = Square { x: 0, y: 4 };
+        let attacks = rook_attacks(rook, Bitboard::from_square(blocker)) & Bitboard::board(8);
+        // Up the file to the blocker, which can be captured, and along the rank
+        assert_eq!(attacks.count(), 3 + 7);
+        assert!(attacks.contains(blocker));
+        assert!(!attacks.contains(Square { x: 0, y: 3 }));
+
+        let bishop = Square { x: 3, y: 3 };
+        assert_eq!(bishop_attacks(bishop, Bitboard::EMPTY).count(), 13);
+        assert_eq!(knight_attacks(Square { x: 0, y: 0 }).count(), 2);
+        // Nothing wraps around from the last file to the first
+        let corner = Square {
+            x: MAX_FILES - 1,
+            y: 0,
+        };
+        assert_eq!(knight_attacks(corner).count(), 2);
+        assert_eq!(bishop_attacks(corner, Bitboard::EMPTY).count(), 7);
+    }
+
+    #[test]
+    fn test_combined_movements() {
+        let square = Square { x: 3, y: 4 };
+        let piece = |kind| Piece {
+            kind,
+            color: PieceColor::White,
+            square,
+        };
+        let board = Bitboard::board(8);
+        let attacks = |kind| piece_attacks(piece(kind), Bitboard::EMPTY) & board;
+        assert_eq!(attacks(PieceType::Queen).count(), 14 + 13);
+        assert_eq!(attacks(PieceType::Archbishop).count(), 13 + 8);
+        assert_eq!(attacks(PieceType::Chancellor).count(), 14 + 8);
+        assert_eq!(
+            attacks(PieceType::Archbishop),
+            attacks(PieceType::Bishop) | attacks(PieceType::Knight)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+use crate::square::*;
+
+/// A set of squares, one bit per square in the order of `Square::index`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Bitboard(pub u128);
+
+/// The squares of the board for each number of files, looked up as move
+/// generation needs them all the time
+const BOARDS: [u128; MAX_FILES as usize + 1] = boards();
+
+const fn boards() -> [u128; MAX_FILES as usize + 1] {
+    let mut boards = [0; MAX_FILES as usize + 1];
+    let mut files = 1;
+    while files <= MAX_FILES as usize {
+        let rank = (1u128 << files) - 1;
+        let mut y = 0;
+        while y < RANKS as usize {
+            boards[files] |= rank << (y * MAX_FILES as usize);
+            y += 1;
+        }
+        files += 1;
+    }
+    boards
+}
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+
+    /// Every square of a board with that many files
+    pub fn board(files: u8) -> Bitboard {
+        Bitboard(BOARDS[files as usize])
+    }
+
+    pub fn from_square(square: Square) -> Bitboard {
+        Bitboard(1 << square.index())
+    }
+
+    pub fn contains(self, square: Square) -> bool {
+        self.0 & (1 << square.index()) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// The square with the lowest index in the set
+    pub fn first(self) -> Option<Square> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Square::from_index(self.0.trailing_zeros() as usize))
+        }
+    }
+
+    pub fn squares(self) -> Squares {
+        Squares(self)
+    }
+}
+
+/// Iterates over the squares of a bitboard, lowest index first
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        let square = self.0.first()?;
+        // Clear the lowest set bit
+        (self.0).0 &= (self.0).0 - 1;
+        Some(square)
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 & other.0)
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 | other.0)
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ other.0)
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Bitboard) {
+        self.0 &= other.0;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Bitboard) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, other: Bitboard) {
+        self.0 ^= other.0;
+    }
+}
@@ -1,215 +1,28 @@
-use std::cmp::{max, min};
-
 use bevy::prelude::*;
 use bevy_mod_picking::*;
 
-use crate::pieces::*;
-
-#[derive(Clone, Copy, PartialEq)]
-pub struct Square {
-    pub x: u8,
-    pub y: u8,
-}
-
-impl Square {
-    fn is_white(&self) -> bool {
-        (self.x + self.y) % 2 == 1
-    }
-}
-
-impl Square {
-    pub fn is_inbetween(&self, square1: Square, square2: Square) -> bool {
-        let min_x = min(square1.x, square2.x);
-        let max_x = max(square1.x, square2.x);
-        let min_y = min(square1.y, square2.y);
-        let max_y = max(square1.y, square2.y);
-        (square1.is_on_same_rank(square2)
-            && self.is_on_same_rank(square1)
-            && (min_x < self.x && self.x < max_x))
-            || (square1.is_on_same_file(square2)
-                && self.is_on_same_file(square1)
-                && (min_y < self.y && self.y < max_y))
-            || (square1.is_diagonal_to(square2)
-                && self.is_diagonal_to(square1)
-                && (min_x < self.x && self.x < max_x)
-                && (min_y < self.y && self.y < max_y))
-    }
-
-    // https://en.wikipedia.org/wiki/Chebyshev_distance
-    pub fn chebyshev_distance_to(&self, square: Square) -> u8 {
-        max(self.rank_distance_to(square), self.file_distance_to(square)) as u8
-    }
-
-    pub fn is_orthogonal_to(&self, square: Square) -> bool {
-        self.is_on_same_rank(square) || self.is_on_same_file(square)
-    }
-
-    pub fn is_diagonal_to(&self, square: Square) -> bool {
-        self.rank_distance_to(square) == self.file_distance_to(square)
-    }
+use chess::crazyhouse::*;
+use chess::game::*;
+use chess::moves::*;
+use chess::piece::*;
+use chess::position::*;
+use chess::square::*;
 
-    pub fn is_on_same_rank(&self, square: Square) -> bool {
-        self.rank_distance_to(square) == 0
-    }
-
-    pub fn is_on_same_file(&self, square: Square) -> bool {
-        self.file_distance_to(square) == 0
-    }
-
-    pub fn rank_distance_to(&self, square: Square) -> u8 {
-        (square.y as i8 - self.y as i8).abs() as u8
-    }
-
-    pub fn file_distance_to(&self, square: Square) -> u8 {
-        (square.x as i8 - self.x as i8).abs() as u8
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::board::*;
-
-    #[test]
-    fn test_is_inbetween() {
-        struct Test {
-            square: Square,
-            square1: Square,
-            square2: Square,
-            result: bool,
-        }
-        let tests = vec![
-            // check all flanking variations
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 0, y: 1 },
-                square2: Square { x: 2, y: 1 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 0, y: 2 },
-                square2: Square { x: 2, y: 0 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 1, y: 2 },
-                square2: Square { x: 1, y: 0 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 2, y: 2 },
-                square2: Square { x: 0, y: 0 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 2, y: 1 },
-                square2: Square { x: 0, y: 1 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 2, y: 0 },
-                square2: Square { x: 0, y: 2 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 1, y: 0 },
-                square2: Square { x: 1, y: 2 },
-                result: true,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 2, y: 2 },
-                result: true,
-            },
-            // check if square is outside line
-            Test {
-                square: Square { x: 0, y: 5 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 0, y: 3 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 0, y: 2 },
-                square1: Square { x: 0, y: 7 },
-                square2: Square { x: 0, y: 4 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 5, y: 0 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 3, y: 0 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 2, y: 0 },
-                square1: Square { x: 7, y: 0 },
-                square2: Square { x: 4, y: 0 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 5, y: 5 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 3, y: 3 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 2, y: 2 },
-                square1: Square { x: 7, y: 7 },
-                square2: Square { x: 4, y: 4 },
-                result: false,
-            },
-            // check if square is the same as the line squares
-            Test {
-                square: Square { x: 0, y: 0 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 0, y: 4 },
-                result: false,
-            },
-            // check when square is not in line, but in between ranks/file
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 0, y: 3 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 0, y: 0 },
-                square2: Square { x: 3, y: 0 },
-                result: false,
-            },
-            Test {
-                square: Square { x: 1, y: 1 },
-                square1: Square { x: 1, y: 2 },
-                square2: Square { x: 3, y: 3 },
-                result: false,
-            },
-        ];
-        for test in tests {
-            assert_eq!(
-                test.square.is_inbetween(test.square1, test.square2),
-                test.result
-            )
-        }
-    }
-}
+use crate::ai::*;
+use crate::fog::*;
+use crate::pieces::*;
 
 fn create_board(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     materials: Res<SquareMaterials>,
+    game: Res<Game>,
 ) {
     let mesh = meshes.add(Mesh::from(shape::Plane { size: 1. }));
 
     // Create squares
-    for i in 0..8 {
-        for j in 0..8 {
+    for i in 0..RANKS {
+        for j in 0..game.position().files() {
             commands
                 .spawn_bundle(PbrBundle {
                     mesh: mesh.clone(),
@@ -227,18 +40,92 @@ fn create_board(
     }
 }
 
+// Component for the square beside the board holding a side's pieces of one
+// kind in Crazyhouse, which are picked up from there to be dropped
+pub struct PocketSlot {
+    pub color: PieceColor,
+    pub kind: PieceType,
+}
+
+/// Where the pocket slot for the pieces of that side and kind is beside a board
+/// with that many files: White's down the right of the board from its own
+/// side, Black's down the left from its own
+pub fn pocket_slot_translation(color: PieceColor, kind: PieceType, files: u8) -> Vec3 {
+    let row = POCKET_PIECES.iter().position(|k| *k == kind).unwrap_or(0) as f32;
+    match color {
+        PieceColor::White => Vec3::new(files as f32 + 0.5, 0., (RANKS - 1) as f32 - row),
+        PieceColor::Black => Vec3::new(-1.5, 0., row),
+    }
+}
+
+fn create_pockets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    materials: Res<SquareMaterials>,
+    game: Res<Game>,
+) {
+    if !game.position().variant().has_drops() {
+        return;
+    }
+    let mesh = meshes.add(Mesh::from(shape::Plane { size: 1. }));
+    let files = game.position().files();
+    for color in [PieceColor::White, PieceColor::Black] {
+        for kind in POCKET_PIECES {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: materials.pocket_square.clone(),
+                    transform: Transform::from_translation(pocket_slot_translation(
+                        color, kind, files,
+                    )),
+                    ..Default::default()
+                })
+                .insert_bundle(PickableBundle::default())
+                .insert(PocketSlot { color, kind });
+        }
+    }
+}
+
+fn color_pocket_slots(
+    selected_drop: Res<SelectedDrop>,
+    game: Res<Game>,
+    materials: Res<SquareMaterials>,
+    mut query: Query<(Entity, &PocketSlot, &mut Handle<StandardMaterial>)>,
+    picking_camera_query: Query<&PickingCamera>,
+) {
+    let top_entity = picking_camera_query
+        .iter()
+        .last()
+        .and_then(|picking_camera| picking_camera.intersect_top())
+        .map(|(entity, _intersection)| entity);
+
+    for (entity, slot, mut material) in query.iter_mut() {
+        let selected =
+            selected_drop.kind == Some(slot.kind) && slot.color == game.position().side_to_move();
+        *material = if selected {
+            materials.selected_square.clone()
+        } else if Some(entity) == top_entity {
+            materials.highlighted_square.clone()
+        } else {
+            materials.pocket_square.clone()
+        };
+    }
+}
+
 fn color_squares(
     selected_square: Res<SelectedSquare>,
     materials: Res<SquareMaterials>,
+    game: Res<Game>,
+    fog: Option<Res<FogOfWar>>,
     mut query: Query<(Entity, &Square, &mut Handle<StandardMaterial>)>,
     picking_camera_query: Query<&PickingCamera>,
 ) {
+    let visible_squares = fog.and_then(|fog| fog.visible_squares(&game));
     // Get entity under the cursor, if there is one
     let top_entity = match picking_camera_query.iter().last() {
-        Some(picking_camera) => match picking_camera.intersect_top() {
-            Some((entity, _intersection)) => Some(entity),
-            None => None,
-        },
+        Some(picking_camera) => picking_camera
+            .intersect_top()
+            .map(|(entity, _intersection)| entity),
         None => None,
     };
 
@@ -246,6 +133,8 @@ fn color_squares(
         // Change the material
         *material = if Some(entity) == selected_square.entity {
             materials.selected_square.clone()
+        } else if visible_squares.map_or(false, |squares| !squares.contains(*square)) {
+            materials.fogged_square.clone()
         } else if Some(entity) == top_entity {
             materials.highlighted_square.clone()
         } else if square.is_white() {
@@ -266,11 +155,20 @@ struct SelectedPiece {
     entity: Option<Entity>,
 }
 
+/// The kind of piece picked up from the pocket of the side to move, to be
+/// dropped on the next square clicked
+#[derive(Default)]
+struct SelectedDrop {
+    kind: Option<PieceType>,
+}
+
 struct SquareMaterials {
     white_square: Handle<StandardMaterial>,
     black_square: Handle<StandardMaterial>,
     highlighted_square: Handle<StandardMaterial>,
     selected_square: Handle<StandardMaterial>,
+    pocket_square: Handle<StandardMaterial>,
+    fogged_square: Handle<StandardMaterial>,
 }
 
 impl FromWorld for SquareMaterials {
@@ -284,23 +182,103 @@ impl FromWorld for SquareMaterials {
             black_square: materials.add(Color::rgb(0., 0.1, 0.1).into()),
             highlighted_square: materials.add(Color::rgb(0.8, 0.3, 0.3).into()),
             selected_square: materials.add(Color::rgb(0.9, 0.1, 0.1).into()),
+            pocket_square: materials.add(Color::rgb(0.5, 0.45, 0.45).into()),
+            fogged_square: materials.add(Color::rgb(0.25, 0.25, 0.3).into()),
         }
     }
 }
 
+/// Sent when a click asks for a move that cannot be made, with the reason why
+pub struct RejectedMove(pub IllegalMove);
+
+/// Sent to make a move, by a player clicking on the board or picking a
+/// promotion, or by the computer
+#[derive(Clone, Copy, Debug)]
+pub enum MoveRequest {
+    Move {
+        from: Square,
+        to: Square,
+        promotion: Option<PieceType>,
+    },
+    Drop {
+        kind: PieceType,
+        to: Square,
+    },
+}
+
+impl From<Move> for MoveRequest {
+    fn from(mv: Move) -> MoveRequest {
+        match mv.kind {
+            MoveKind::Drop(kind) => MoveRequest::Drop { kind, to: mv.to },
+            _ => MoveRequest::Move {
+                from: mv.from,
+                to: mv.to,
+                promotion: mv.promotion(),
+            },
+        }
+    }
+}
+
+/// Makes the moves asked for, or tells why they cannot be made
+fn make_requested_moves(
+    mut requests: EventReader<MoveRequest>,
+    mut game: ResMut<Game>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut rejected_moves: EventWriter<RejectedMove>,
+) {
+    for request in requests.iter() {
+        let position = *game.position();
+        let result = match *request {
+            MoveRequest::Move {
+                from,
+                to,
+                promotion,
+            } => position.check_move(from, to, promotion),
+            MoveRequest::Drop { kind, to } => position.check_drop(kind, to),
+        }
+        .and_then(|mv| game.make_move(mv));
+        match (result, *request) {
+            (Ok(()), _) => {}
+            // The move is made once the player picks what to promote to
+            (Err(IllegalMove::PromotionNeeded), MoveRequest::Move { from, to, .. }) => {
+                pending_promotion.squares = Some((from, to))
+            }
+            (Err(reason), _) => rejected_moves.send(RejectedMove(reason)),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn select_square(
-    mut commands: Commands,
     mouse_button_inputs: Res<Input<MouseButton>>,
     mut selected_square: ResMut<SelectedSquare>,
     mut selected_piece: ResMut<SelectedPiece>,
+    mut selected_drop: ResMut<SelectedDrop>,
+    game: Res<Game>,
+    pending_promotion: Res<PendingPromotion>,
+    mut move_requests: EventWriter<MoveRequest>,
+    mut rejected_moves: EventWriter<RejectedMove>,
     squares_query: Query<&Square>,
+    pocket_slots_query: Query<&PocketSlot>,
     picking_camera_query: Query<&PickingCamera>,
-    mut pieces_query: Query<(Entity, &mut Piece)>,
+    pieces_query: Query<(Entity, &Piece)>,
+    fog: Option<Res<FogOfWar>>,
+    computer: Option<Res<ComputerPlayer>>,
 ) {
-    // Only run if the left button is pressed
-    if !mouse_button_inputs.just_pressed(MouseButton::Left) {
+    // Only run if the left button is pressed, no promotion is being picked,
+    // the game is still going on, the screen is not being handed over and it
+    // is not the computer's turn
+    let side_to_move = game.position().side_to_move();
+    if !mouse_button_inputs.just_pressed(MouseButton::Left)
+        || pending_promotion.squares.is_some()
+        || game.is_over()
+        || fog.as_ref().map_or(false, |fog| fog.is_handing_over(&game))
+        || computer.map_or(false, |computer| computer.plays(side_to_move))
+    {
         return;
     }
+    // Squares hidden by the fog of war are as good as empty to the player
+    let visible_squares = fog.and_then(|fog| fog.visible_squares(&game));
 
     // Get the square under the cursor and set it as the selected
     if let Some(picking_camera) = picking_camera_query.iter().last() {
@@ -309,59 +287,100 @@ fn select_square(
                 // Mark it as selected
                 selected_square.entity = Some(square_entity);
 
-                if let Some(selected_piece_entity) = selected_piece.entity {
-                    let pieces = pieces_query.iter_mut().map(|(_, piece)| *piece).collect();
-                    let piece_entities: Vec<(Entity, Piece)> = pieces_query
-                        .iter_mut()
-                        .map(|(entity, piece)| (entity, *piece))
-                        .collect();
-
+                if let Some(kind) = selected_drop.kind.take() {
+                    // Drop the piece picked up from the pocket on the square
+                    move_requests.send(MoveRequest::Drop { kind, to: *square });
+                    selected_square.entity = None;
+                } else if let Some(selected_piece_entity) = selected_piece.entity {
                     // Move the selected piece to the selected square
-                    if let Ok((_piece_entity, mut piece)) =
-                        pieces_query.get_mut(selected_piece_entity)
-                    {
-                        if piece.is_valid_move(*square, pieces) {
-                            // Check if a piece of the opposite color exists in this square and despawn it
-                            for (other_entity, other_piece) in piece_entities {
-                                if other_piece.square == *square && other_piece.color != piece.color
-                                {
-                                    // Despawn piece
-                                    commands.entity(other_entity).despawn_recursive();
-                                }
-                            }
-                            piece.square = *square;
-                        }
+                    // Clicking the selected piece again just deselects it
+                    let piece = pieces_query
+                        .get(selected_piece_entity)
+                        .ok()
+                        .filter(|(_piece_entity, piece)| piece.square != *square);
+                    if let Some((_piece_entity, piece)) = piece {
+                        move_requests.send(MoveRequest::Move {
+                            from: piece.square,
+                            to: *square,
+                            promotion: None,
+                        });
                     }
                     selected_square.entity = None;
                     selected_piece.entity = None;
                 } else {
-                    // Select the piece in the currently selected square
-                    for (piece_entity, piece) in pieces_query.iter_mut() {
-                        if piece.square == *square {
+                    // Select the piece in the currently selected square, as long as it is
+                    // the turn of that piece's color
+                    for (piece_entity, piece) in pieces_query.iter() {
+                        if piece.square != *square
+                            || visible_squares.map_or(false, |squares| !squares.contains(*square))
+                        {
+                            continue;
+                        }
+                        if piece.color == game.position().side_to_move() {
                             // piece_entity is now the entity in the same square
                             selected_piece.entity = Some(piece_entity);
-                            break;
+                        } else {
+                            rejected_moves.send(RejectedMove(IllegalMove::NotYourTurn));
                         }
+                        break;
                     }
                 }
+            } else if let Ok(slot) = pocket_slots_query.get(square_entity) {
+                // Pick up a piece from the pocket, to drop on the next square
+                // clicked
+                selected_square.entity = None;
+                selected_piece.entity = None;
+                selected_drop.kind = None;
+                let position = game.position();
+                if slot.color != position.side_to_move() {
+                    rejected_moves.send(RejectedMove(IllegalMove::NotYourTurn));
+                } else if position.pocket(slot.color).count(slot.kind) == 0 {
+                    rejected_moves.send(RejectedMove(IllegalMove::NotInPocket));
+                } else {
+                    selected_drop.kind = Some(slot.kind);
+                }
             }
         } else {
             // Player clicked outside the board, deselect everything
             selected_square.entity = None;
             selected_piece.entity = None;
+            selected_drop.kind = None;
         }
     }
 }
 
+/// Forgets the selection once the pieces change, as the selected piece may
+/// have moved or gone, for example after a move is taken back
+fn clear_selection(
+    game: Res<Game>,
+    mut selected_square: ResMut<SelectedSquare>,
+    mut selected_piece: ResMut<SelectedPiece>,
+    mut selected_drop: ResMut<SelectedDrop>,
+) {
+    if game.is_changed() {
+        selected_square.entity = None;
+        selected_piece.entity = None;
+        selected_drop.kind = None;
+    }
+}
+
 pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<SelectedSquare>()
             .init_resource::<SelectedPiece>()
+            .init_resource::<SelectedDrop>()
             .init_resource::<SquareMaterials>()
+            .init_resource::<Game>()
+            .add_event::<RejectedMove>()
+            .add_event::<MoveRequest>()
             .add_startup_system(create_board.system())
+            .add_startup_system(create_pockets.system())
             .add_system(color_squares.system())
-            .add_system(select_square.system());
+            .add_system(color_pocket_slots.system())
+            .add_system(select_square.system())
+            .add_system(make_requested_moves.system())
+            .add_system(clear_selection.system());
     }
 }
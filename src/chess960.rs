@@ -0,0 +1,175 @@
+//! Chess960, or Fischer Random Chess, starts from one of 960 shuffled back
+//! ranks: the bishops on opposite colors and the king somewhere between the
+//! rooks, with Black mirroring White. The positions are numbered 0 to 959 the
+//! standard way, with 518 being the classical setup.
+
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+pub const CHESS960_POSITIONS: u16 = 960;
+
+/// Where the two knights go among the five squares left once the bishops and
+/// queen are placed
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// The back rank of the numbered starting position, from the a-file to the
+/// h-file, or nothing if the number is not below 960
+pub fn back_rank(index: u16) -> Option<[PieceType; 8]> {
+    if index >= CHESS960_POSITIONS {
+        return None;
+    }
+    let mut rank = [None; 8];
+    let mut n = index as usize;
+    // The light-squared bishop takes the b-, d-, f- or h-file, and the
+    // dark-squared one the a-, c-, e- or g-file
+    rank[n % 4 * 2 + 1] = Some(PieceType::Bishop);
+    n /= 4;
+    rank[n % 4 * 2] = Some(PieceType::Bishop);
+    n /= 4;
+    let mut place = |kind, nth_empty: usize| {
+        let x = (0..8)
+            .filter(|x| rank[*x].is_none())
+            .nth(nth_empty)
+            .unwrap();
+        rank[x] = Some(kind);
+    };
+    place(PieceType::Queen, n % 6);
+    n /= 6;
+    let (first, second) = KNIGHT_PLACEMENTS[n];
+    // Placing the second knight first keeps the first one's count right
+    place(PieceType::Knight, second);
+    place(PieceType::Knight, first);
+    // The king goes between the rooks on the three squares left
+    for kind in [PieceType::Rook, PieceType::King, PieceType::Rook] {
+        place(kind, 0);
+    }
+    let mut kinds = [PieceType::Pawn; 8];
+    for (kind, square) in kinds.iter_mut().zip(rank.iter()) {
+        *kind = square.unwrap();
+    }
+    Some(kinds)
+}
+
+impl Position {
+    /// The numbered Chess960 starting position, with both sides able to
+    /// castle with either rook
+    pub fn chess960(index: u16) -> Option<Position> {
+        let back_rank = back_rank(index)?;
+        let mut pieces = Vec::new();
+        let mut castling_rights = CastlingRights::NONE;
+        for color in [PieceColor::White, PieceColor::Black] {
+            let home = color.home_rank();
+            let pawn_rank = (home as i8 + color.forward()) as u8;
+            for (x, kind) in back_rank.iter().enumerate() {
+                let x = x as u8;
+                pieces.push(Piece {
+                    kind: *kind,
+                    color,
+                    square: Square { x, y: home },
+                });
+                pieces.push(Piece {
+                    kind: PieceType::Pawn,
+                    color,
+                    square: Square { x, y: pawn_rank },
+                });
+            }
+            let mut rooks = (0..8).filter(|x| back_rank[*x as usize] == PieceType::Rook);
+            *castling_rights.rook_file_mut(color, CastlingSide::Queenside) = rooks.next();
+            *castling_rights.rook_file_mut(color, CastlingSide::Kingside) = rooks.next();
+        }
+        Some(Position::new(
+            pieces,
+            PieceColor::White,
+            castling_rights,
+            None,
+        ))
+    }
+
+    /// Whether a side may still castle from somewhere other than the
+    /// classical squares, which only Chess960 allows. Those are the corners
+    /// for the rooks and the file just right of the middle for the king.
+    pub fn has_chess960_castling(&self) -> bool {
+        let rights = self.castling_rights();
+        let last_file = self.files() - 1;
+        [PieceColor::White, PieceColor::Black].iter().any(|color| {
+            let king = self.pieces_of(PieceType::King, *color).first();
+            let moved_rook = [
+                (CastlingSide::Kingside, last_file),
+                (CastlingSide::Queenside, 0),
+            ]
+            .iter()
+            .any(|(side, x)| matches!(rights.rook_file(*color, *side), Some(file) if file != *x));
+            let may_castle = CASTLING_SIDES
+                .iter()
+                .any(|side| rights.can_castle(*color, *side));
+            moved_rook || (may_castle && king.map_or(false, |square| square.x != self.files() / 2))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::chess960::*;
+    use crate::fen::*;
+
+    #[test]
+    fn test_back_rank() {
+        struct Test {
+            index: u16,
+            fen: &'static str,
+        }
+        let tests = vec![
+            Test {
+                index: 518,
+                fen: START_FEN,
+            },
+            Test {
+                index: 0,
+                fen: "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1",
+            },
+            Test {
+                index: 959,
+                fen: "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1",
+            },
+        ];
+        for test in tests {
+            assert_eq!(Position::chess960(test.index).unwrap().to_fen(), test.fen);
+            assert_eq!(
+                Position::chess960(test.index)
+                    .unwrap()
+                    .has_chess960_castling(),
+                test.index != 518
+            );
+        }
+        assert_eq!(Position::chess960(960), None);
+
+        let mut back_ranks = HashSet::new();
+        for index in 0..CHESS960_POSITIONS {
+            let rank = back_rank(index).unwrap();
+            let files = |kind| (0..8).filter(move |x| rank[*x] == kind);
+            let bishops: Vec<usize> = files(PieceType::Bishop).collect();
+            let rooks: Vec<usize> = files(PieceType::Rook).collect();
+            let king = files(PieceType::King).next().unwrap();
+            assert_eq!(bishops.len(), 2);
+            assert_ne!(bishops[0] % 2, bishops[1] % 2, "bishops on opposite colors");
+            assert!(rooks[0] < king && king < rooks[1], "king between the rooks");
+            assert_eq!(files(PieceType::Knight).count(), 2);
+            back_ranks.insert(rank);
+        }
+        assert_eq!(back_ranks.len(), 960);
+    }
+}
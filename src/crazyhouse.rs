@@ -0,0 +1,150 @@
+//! Crazyhouse: a captured piece changes sides and goes into the capturer's
+//! pocket, and instead of moving, a side may drop a piece from its pocket onto
+//! any empty square. Pawns cannot be dropped on the first or last rank, and a
+//! piece that came from a promotion goes back into the pocket as a pawn.
+
+use crate::piece::*;
+
+/// The pieces that can be held in a pocket, in the order FEN writes them
+pub const POCKET_PIECES: [PieceType; 7] = [
+    PieceType::Queen,
+    PieceType::Chancellor,
+    PieceType::Archbishop,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+/// The most pieces of one kind a pocket can hold: promoted pieces go back as
+/// pawns, so there are never more than the 16 pawns a game starts with
+pub const POCKET_CAPACITY: u8 = 16;
+
+/// The captured pieces a side holds, counted by kind
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Pocket([u8; PIECE_TYPES.len()]);
+
+impl Pocket {
+    pub const EMPTY: Pocket = Pocket([0; PIECE_TYPES.len()]);
+
+    pub fn count(&self, kind: PieceType) -> u8 {
+        self.0[kind as usize]
+    }
+
+    pub fn add(&mut self, kind: PieceType) {
+        let count = &mut self.0[kind as usize];
+        debug_assert!(*count < POCKET_CAPACITY, "pocket is full of {:?}", kind);
+        *count = count.saturating_add(1);
+    }
+
+    /// Takes out a piece of the kind, if there is one
+    pub fn remove(&mut self, kind: PieceType) {
+        let count = &mut self.0[kind as usize];
+        *count = count.saturating_sub(1);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|count| *count == 0)
+    }
+
+    /// The kinds of piece in the pocket, most valuable first
+    pub fn kinds(self) -> impl Iterator<Item = PieceType> {
+        POCKET_PIECES
+            .iter()
+            .copied()
+            .filter(move |kind| self.count(*kind) > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crazyhouse::*;
+    use crate::game::*;
+    use crate::moves::*;
+    use crate::position::*;
+    use crate::square::*;
+    use crate::variant::*;
+
+    fn play(game: &mut Game, san: &str) {
+        let mv = game.position().parse_san(san).unwrap();
+        game.make_move(mv).unwrap();
+    }
+
+    #[test]
+    fn test_drops() {
+        let mut game = Game::new(Position::default().with_variant(Variant::Crazyhouse));
+        assert_eq!(
+            game.position().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1"
+        );
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3"] {
+            play(&mut game, san);
+        }
+        let pocket = |game: &Game, color| game.position().pocket(color);
+        assert_eq!(pocket(&game, PieceColor::White).count(PieceType::Pawn), 1);
+        assert_eq!(pocket(&game, PieceColor::Black).count(PieceType::Pawn), 1);
+
+        let square = |name: &str| name.parse::<Square>().unwrap();
+        let position = *game.position();
+        assert_eq!(
+            position.check_drop(PieceType::Pawn, square("d5")),
+            Err(IllegalMove::OccupiedSquare)
+        );
+        assert_eq!(
+            position.check_drop(PieceType::Pawn, square("d8")),
+            Err(IllegalMove::PawnDropOnBackRank)
+        );
+        assert_eq!(
+            position.check_drop(PieceType::Knight, square("e4")),
+            Err(IllegalMove::NotInPocket)
+        );
+        let drop = position.check_drop(PieceType::Pawn, square("e4")).unwrap();
+        assert_eq!(drop.kind, MoveKind::Drop(PieceType::Pawn));
+        assert_eq!(drop.to_string(), "P@e4");
+        assert_eq!(position.to_san(drop), "P@e4");
+        assert_eq!(position.parse_uci("P@e4"), Ok(drop));
+        assert_eq!(position.parse_san("@e4"), Ok(drop));
+
+        play(&mut game, "P@e4");
+        assert!(pocket(&game, PieceColor::Black).is_empty());
+        assert_eq!(
+            game.position().to_fen(),
+            "rnb1kbnr/ppp1pppp/8/3q4/4p3/2N5/PPPP1PPP/R1BQKBNR[P] w KQkq - 0 4"
+        );
+        game.undo();
+        assert_eq!(*game.position(), position);
+    }
+
+    #[test]
+    fn test_promoted_pieces() {
+        let fen = "4k3/1P6/8/8/8/8/8/4K3[] w - - 0 1";
+        let position = Position::from_fen(fen)
+            .unwrap()
+            .with_variant(Variant::Crazyhouse);
+        assert_eq!(position.to_fen(), fen);
+        let mut game = Game::new(position);
+        play(&mut game, "b8=Q+");
+        assert!(game.position().to_fen().starts_with("1Q~2k3/"));
+        // The promoted queen goes back to being a pawn once captured
+        for san in ["Kd7", "Qc8+", "Kxc8"] {
+            play(&mut game, san);
+        }
+        assert_eq!(
+            game.position()
+                .pocket(PieceColor::Black)
+                .kinds()
+                .collect::<Vec<_>>(),
+            vec![PieceType::Pawn]
+        );
+
+        // A drop that blocks check saves the king from checkmate
+        let fen = "k7/2K5/8/8/8/8/8/R7[n] b - - 0 1";
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(position.to_fen(), fen);
+        assert!(!position.has_valid_move(), "only Crazyhouse has drops");
+        let position = position.with_variant(Variant::Crazyhouse);
+        assert!(position.is_in_check());
+        assert_eq!(position.legal_moves().len(), 6);
+        assert!(!Game::new(position).is_over());
+    }
+}
@@ -0,0 +1,131 @@
+//! Judges a position without looking any further ahead, in centipawns from the
+//! point of view of the side to move. Material counts for the most, with small
+//! bonuses for pieces near the centre and pawns close to promoting, and a few
+//! terms for what wins in each variant.
+
+use std::cmp::max;
+
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+use crate::variant::*;
+
+/// A win worth more than any material, less the number of moves to it
+pub const MATE: i32 = 100_000;
+
+/// How much a piece of the kind is worth, in centipawns. The king is never
+/// traded, so it counts for nothing.
+pub fn piece_value(kind: PieceType) -> i32 {
+    match kind {
+        PieceType::King => 0,
+        PieceType::Queen => 900,
+        PieceType::Rook => 500,
+        PieceType::Bishop => 330,
+        PieceType::Knight => 320,
+        PieceType::Pawn => 100,
+        PieceType::Archbishop => 825,
+        PieceType::Chancellor => 875,
+    }
+}
+
+/// Up to 14 for the middle of the board, down to nothing on the edges
+fn centrality(square: Square, files: u8) -> i32 {
+    let from_middle = |coordinate: u8, size: u8| (2 * coordinate as i32 - (size as i32 - 1)).abs();
+    let worst = max(files, RANKS) as i32 - 1;
+    let distance = max(from_middle(square.x, files), from_middle(square.y, RANKS));
+    (worst - distance) * 14 / worst
+}
+
+/// What the side's pieces are worth where they stand
+fn score_side(position: &Position, color: PieceColor) -> i32 {
+    let variant = position.variant();
+    let mut score = 0;
+    for kind in PIECE_TYPES {
+        for square in position.pieces_of(kind, color).squares() {
+            score += piece_value(kind);
+            score += match kind {
+                // Pawns are worth more the closer they are to promoting
+                PieceType::Pawn => {
+                    let start = color.home_rank() as i32 + color.forward() as i32;
+                    (square.y as i32 - start) * color.forward() as i32 * 8
+                        + centrality(square, position.files())
+                }
+                // The king is safest at home, apart from when it has a hill
+                // to climb
+                PieceType::King if variant == Variant::KingOfTheHill => {
+                    centrality(square, position.files()) * 20
+                }
+                PieceType::King => 0,
+                _ => centrality(square, position.files()) * 2,
+            };
+        }
+    }
+    // Pieces in the pocket can go anywhere, which is worth a little extra
+    let pocket = position.pocket(color);
+    for kind in pocket.kinds() {
+        score += pocket.count(kind) as i32 * piece_value(kind) * 11 / 10;
+    }
+    if variant == Variant::ThreeCheck {
+        score += position.checks_given(color) as i32 * 300;
+    }
+    score
+}
+
+/// The score of the position for the side to move
+pub fn evaluate(position: &Position) -> i32 {
+    let side_to_move = position.side_to_move();
+    let score = score_side(position, side_to_move) - score_side(position, side_to_move.opponent());
+    // Losing pieces is how Antichess is won
+    if position.variant() == Variant::Antichess {
+        -score
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluation::*;
+    use crate::fen::*;
+
+    #[test]
+    fn test_evaluate() {
+        struct Test {
+            fen: &'static str,
+            score: fn(i32) -> bool,
+        }
+        let tests = vec![
+            // Both sides mirror each other
+            Test {
+                fen: START_FEN,
+                score: |score| score == 0,
+            },
+            Test {
+                fen: CAPABLANCA_FEN,
+                score: |score| score == 0,
+            },
+            // Black is missing its queen
+            Test {
+                fen: "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                score: |score| score > 800,
+            },
+            Test {
+                fen: "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+                score: |score| score < -800,
+            },
+            // A pawn about to promote is worth more than one at home
+            Test {
+                fen: "4k3/P7/8/8/8/8/7p/4K3 w - - 0 1",
+                score: |score| score == 0,
+            },
+            Test {
+                fen: "4k3/P6p/8/8/8/8/8/4K3 w - - 0 1",
+                score: |score| score > 0,
+            },
+        ];
+        for test in tests {
+            let score = evaluate(&Position::from_fen(test.fen).unwrap());
+            assert!((test.score)(score), "{} scores {}", test.fen, score);
+        }
+    }
+}
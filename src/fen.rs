@@ -0,0 +1,683 @@
+//! Forsyth-Edwards Notation, which writes a position on one line: the pieces
+//! rank by rank from Black's side of the board, the side to move, castling
+//! rights, the en passant square and the two move clocks. The first rank
+//! written is y = 0 and the last is White's back rank at y = 7, so FEN ranks
+//! map straight onto `Square { x, y }`.
+//!
+//! Chess960 castling rights are read and written as X-FEN, which keeps `KQkq`
+//! for the outermost rook on each side and names the rook's file otherwise,
+//! and can also be written as Shredder-FEN, which always names the file.
+//!
+//! Boards wider than eight files have wider ranks, with a number of ten or
+//! more for a run of empty squares, like `rnabqkbcnr/pppppppppp/10/...` in
+//! Capablanca chess.
+//!
+//! Crazyhouse positions add the pockets in brackets after the pieces, like
+//! `[QNp]`, and mark promoted pieces with a `~` after their letter.
+
+use std::fmt;
+
+use crate::bitboard::*;
+use crate::crazyhouse::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+use crate::variant::*;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The starting position of Capablanca chess, on ten files with an
+/// archbishop and a chancellor each
+pub const CAPABLANCA_FEN: &str =
+    "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+    /// FEN has six fields separated by spaces, though the two clocks are
+    /// often left out
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    /// The rank, numbered from 1 on White's side, covers a different number
+    /// of squares from most of the others, or more than the widest board
+    WrongRankLength(u8),
+    InvalidPiece(char),
+    /// The side has no king, which every position needs
+    MissingKing(PieceColor),
+    /// The side has more than one king
+    ExtraKing(PieceColor),
+    /// A pawn stands on the first or last rank, where it can never be
+    PawnOnBackRank(Square),
+    /// Pockets missing their closing bracket, holding a king, or holding
+    /// more than 16 pieces of a kind
+    InvalidPocket(String),
+    InvalidSideToMove(String),
+    InvalidCastlingRights(String),
+    /// A castling right, given by its letter, without a king and a rook on
+    /// the back rank to castle with
+    NoCastlingRook(char),
+    InvalidEnPassantSquare(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => {
+                write!(
+                    f,
+                    "expected 4 or 6 fields separated by spaces, found {}",
+                    count
+                )
+            }
+            FenError::WrongRankCount(count) => {
+                write!(f, "expected 8 ranks separated by '/', found {}", count)
+            }
+            FenError::WrongRankLength(rank) => {
+                write!(
+                    f,
+                    "rank {} does not have as many squares as the others",
+                    rank
+                )
+            }
+            FenError::InvalidPiece(c) => write!(f, "'{}' is not a piece", c),
+            FenError::MissingKing(color) => write!(f, "{} has no king", color_name(*color)),
+            FenError::ExtraKing(color) => {
+                write!(f, "{} has more than one king", color_name(*color))
+            }
+            FenError::PawnOnBackRank(square) => write!(
+                f,
+                "there is a pawn on {}, but pawns cannot stand on the first or last rank",
+                square
+            ),
+            FenError::InvalidPocket(field) => write!(f, "'{}' are not valid pockets", field),
+            FenError::InvalidSideToMove(field) => {
+                write!(f, "side to move must be 'w' or 'b', not '{}'", field)
+            }
+            FenError::InvalidCastlingRights(field) => write!(
+                f,
+                "castling rights must be '-', or some of 'KQkq' and the rook files 'A'-'P' and \
+                 'a'-'p', each at most once, not '{}'",
+                field
+            ),
+            FenError::NoCastlingRook(c) => write!(
+                f,
+                "there is no king and rook on the back rank for the castling right '{}'",
+                c
+            ),
+            FenError::InvalidEnPassantSquare(field) => write!(
+                f,
+                "en passant square must be '-' or the square a pawn of the other side just \
+                 passed, on rank 6 with White to move or rank 3 with Black, not '{}'",
+                field
+            ),
+            FenError::InvalidHalfmoveClock(field) => {
+                write!(f, "halfmove clock must be a number, not '{}'", field)
+            }
+            FenError::InvalidFullmoveNumber(field) => write!(
+                f,
+                "fullmove number must be a number from 1 up, not '{}'",
+                field
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Position {
+    /// Reads a position played under the standard rules
+    pub fn from_fen(fen: &str) -> Result<Position, FenError> {
+        Position::from_variant_fen(fen, Variant::Standard)
+    }
+
+    /// Reads a position played under the rules of the variant, which decide
+    /// whether each side needs a king
+    pub fn from_variant_fen(fen: &str, variant: Variant) -> Result<Position, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+        let (placement, pockets) = match fields[0].find('[') {
+            Some(start) => (&fields[0][..start], parse_pockets(&fields[0][start..])?),
+            None => (fields[0], [Pocket::EMPTY; 2]),
+        };
+        let (pieces, promoted, files) = parse_placement(placement)?;
+        check_pieces(&pieces, variant)?;
+        let side_to_move = match fields[1] {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            field => return Err(FenError::InvalidSideToMove(field.to_string())),
+        };
+        let castling_rights = parse_castling_rights(fields[2], &pieces)?;
+        let en_passant_square = match fields[3] {
+            "-" => None,
+            field => match field.parse::<Square>() {
+                Ok(square) if is_en_passant_target(&pieces, files, side_to_move, square) => {
+                    Some(square)
+                }
+                _ => return Err(FenError::InvalidEnPassantSquare(field.to_string())),
+            },
+        };
+        let (halfmove_clock, fullmove_number) = if fields.len() == 6 {
+            let halfmove_clock = fields[4]
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            let fullmove_number = match fields[5].parse() {
+                Ok(number) if number >= 1 => number,
+                _ => return Err(FenError::InvalidFullmoveNumber(fields[5].to_string())),
+            };
+            (halfmove_clock, fullmove_number)
+        } else {
+            (0, 1)
+        };
+        Ok(
+            Position::new(pieces, side_to_move, castling_rights, en_passant_square)
+                .with_clocks(halfmove_clock, fullmove_number)
+                .with_variant(variant)
+                .with_files(files)
+                .with_pockets(pockets, promoted),
+        )
+    }
+
+    /// Writes the position as FEN, using X-FEN for the castling rights
+    pub fn to_fen(&self) -> String {
+        self.fen_with_castling(self.castling_field(false))
+    }
+
+    /// Writes the position as Shredder-FEN, where castling rights are the
+    /// files of the rooks, like `HAha` for the standard starting position
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen_with_castling(self.castling_field(true))
+    }
+
+    fn castling_field(&self, shredder: bool) -> String {
+        let rights = self.castling_rights();
+        let mut field = String::new();
+        for color in [PieceColor::White, PieceColor::Black] {
+            let rank = color.home_rank();
+            for side in CASTLING_SIDES {
+                let x = match rights.rook_file(color, side) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let outermost = self
+                    .pieces_of(PieceType::Rook, color)
+                    .squares()
+                    .filter(|rook| rook.y == rank)
+                    .all(|rook| match side {
+                        CastlingSide::Kingside => rook.x <= x,
+                        CastlingSide::Queenside => rook.x >= x,
+                    });
+                let c = match side {
+                    _ if shredder || !outermost => (b'a' + x) as char,
+                    CastlingSide::Kingside => 'k',
+                    CastlingSide::Queenside => 'q',
+                };
+                field.push(match color {
+                    PieceColor::White => c.to_ascii_uppercase(),
+                    PieceColor::Black => c,
+                });
+            }
+        }
+        if field.is_empty() {
+            field.push('-');
+        }
+        field
+    }
+
+    fn fen_with_castling(&self, castling_rights: String) -> String {
+        let mut placement = String::new();
+        for y in 0..RANKS {
+            let mut empty = 0;
+            for x in 0..self.files() {
+                match self.piece_at(Square { x, y }) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement += &empty.to_string();
+                            empty = 0;
+                        }
+                        placement.push(piece.letter());
+                        if self.promoted().contains(piece.square) {
+                            placement.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement += &empty.to_string();
+            }
+            if y < RANKS - 1 {
+                placement.push('/');
+            }
+        }
+        let colors = [PieceColor::White, PieceColor::Black];
+        if self.variant().has_drops() || colors.iter().any(|c| !self.pocket(*c).is_empty()) {
+            placement.push('[');
+            for color in colors {
+                let pocket = self.pocket(color);
+                for kind in pocket.kinds() {
+                    let letter = match color {
+                        PieceColor::White => kind.letter(),
+                        PieceColor::Black => kind.letter().to_ascii_lowercase(),
+                    };
+                    for _ in 0..pocket.count(kind) {
+                        placement.push(letter);
+                    }
+                }
+            }
+            placement.push(']');
+        }
+
+        let side_to_move = match self.side_to_move() {
+            PieceColor::White => "w",
+            PieceColor::Black => "b",
+        };
+        let en_passant_square = match self.en_passant_square() {
+            Some(square) => square.to_string(),
+            None => "-".to_string(),
+        };
+        format!(
+            "{} {} {} {} {} {}",
+            placement,
+            side_to_move,
+            castling_rights,
+            en_passant_square,
+            self.halfmove_clock(),
+            self.fullmove_number()
+        )
+    }
+}
+
+/// Reads the pockets of both sides, written in brackets
+fn parse_pockets(field: &str) -> Result<[Pocket; 2], FenError> {
+    let invalid = || FenError::InvalidPocket(field.to_string());
+    let letters = field
+        .strip_prefix('[')
+        .and_then(|field| field.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let mut pockets = [Pocket::EMPTY; 2];
+    for c in letters.chars() {
+        let kind = PieceType::from_letter(c).ok_or(FenError::InvalidPiece(c))?;
+        if kind == PieceType::King {
+            return Err(invalid());
+        }
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        let pocket = &mut pockets[color as usize];
+        if pocket.count(kind) == POCKET_CAPACITY {
+            return Err(invalid());
+        }
+        pocket.add(kind);
+    }
+    Ok(pockets)
+}
+
+/// Reads the pieces on the board, which of them are marked as promoted, and
+/// the number of files, which every rank must agree on
+fn parse_placement(field: &str) -> Result<(Vec<Piece>, Bitboard, u8), FenError> {
+    let ranks: Vec<&str> = field.split('/').collect();
+    if ranks.len() != RANKS as usize {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut promoted = Bitboard::EMPTY;
+    let mut widths = Vec::new();
+    for (y, rank) in ranks.iter().enumerate() {
+        let wrong_length = FenError::WrongRankLength(RANKS - y as u8);
+        let mut x: u8 = 0;
+        let mut chars = rank.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '~' {
+                let square = Square {
+                    x: x.wrapping_sub(1),
+                    y: y as u8,
+                };
+                match pieces.last() {
+                    Some(piece) if piece.square == square => {
+                        promoted |= Bitboard::from_square(square)
+                    }
+                    _ => return Err(FenError::InvalidPiece(c)),
+                }
+                continue;
+            }
+            if x >= MAX_FILES {
+                return Err(wrong_length);
+            }
+            match c.to_digit(10) {
+                Some(digit @ 1..=9) => {
+                    let mut empty = digit;
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        empty = empty * 10 + digit;
+                        chars.next();
+                    }
+                    if empty > (MAX_FILES - x) as u32 {
+                        return Err(wrong_length);
+                    }
+                    x += empty as u8;
+                }
+                _ => {
+                    let kind = PieceType::from_letter(c).ok_or(FenError::InvalidPiece(c))?;
+                    let color = if c.is_ascii_uppercase() {
+                        PieceColor::White
+                    } else {
+                        PieceColor::Black
+                    };
+                    pieces.push(Piece {
+                        kind,
+                        color,
+                        square: Square { x, y: y as u8 },
+                    });
+                    x += 1;
+                }
+            }
+        }
+        widths.push(x);
+    }
+    // The ranks that are out of line are the ones not as wide as most
+    let files = widths
+        .iter()
+        .copied()
+        .max_by_key(|width| widths.iter().filter(|other| *other == width).count())
+        .unwrap_or(0);
+    if let Some(y) = widths
+        .iter()
+        .position(|width| *width != files || files == 0)
+    {
+        return Err(FenError::WrongRankLength(RANKS - y as u8));
+    }
+    Ok((pieces, promoted, files))
+}
+
+/// Checks that the pieces could stand like that in a game of the variant:
+/// each side has exactly one king, unless it is an ordinary piece, and no
+/// pawn is on the first or last rank
+fn check_pieces(pieces: &[Piece], variant: Variant) -> Result<(), FenError> {
+    // Without a royal king, any number of kings may be on the board
+    if variant.has_royal_king() {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let kings = pieces
+                .iter()
+                .filter(|piece| piece.kind == PieceType::King && piece.color == color)
+                .count();
+            match kings {
+                0 => return Err(FenError::MissingKing(color)),
+                1 => {}
+                _ => return Err(FenError::ExtraKing(color)),
+            }
+        }
+    }
+    match pieces.iter().find(|piece| {
+        piece.kind == PieceType::Pawn && (piece.square.y == 0 || piece.square.y == RANKS - 1)
+    }) {
+        Some(pawn) => Err(FenError::PawnOnBackRank(pawn.square)),
+        None => Ok(()),
+    }
+}
+
+/// Whether a pawn of the other side can just have passed over the square
+/// with a double push: the square is on the sixth rank with White to move or
+/// the third with Black, on the board, with the pawn right in front of it
+fn is_en_passant_target(
+    pieces: &[Piece],
+    files: u8,
+    side_to_move: PieceColor,
+    square: Square,
+) -> bool {
+    let (target_y, pawn_y) = match side_to_move {
+        PieceColor::White => (2, 3),
+        PieceColor::Black => (5, 4),
+    };
+    let pawn = Square {
+        x: square.x,
+        y: pawn_y,
+    };
+    square.y == target_y
+        && square.x < files
+        && pieces.iter().any(|piece| {
+            piece.kind == PieceType::Pawn && piece.color != side_to_move && piece.square == pawn
+        })
+}
+
+/// Reads `KQkq` as the outermost rook on each side of the king, and file
+/// letters as the rook on that file, upper case for White
+fn parse_castling_rights(field: &str, pieces: &[Piece]) -> Result<CastlingRights, FenError> {
+    let invalid = || FenError::InvalidCastlingRights(field.to_string());
+    let mut rights = CastlingRights::NONE;
+    if field == "-" {
+        return Ok(rights);
+    }
+    for c in field.chars() {
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        let on_back_rank = |kind| {
+            pieces
+                .iter()
+                .filter(move |piece| {
+                    piece.kind == kind
+                        && piece.color == color
+                        && piece.square.y == color.home_rank()
+                })
+                .map(|piece| piece.square.x)
+        };
+        let no_rook = || FenError::NoCastlingRook(c);
+        let king = on_back_rank(PieceType::King).next().ok_or_else(no_rook)?;
+        let (side, rook) = match c.to_ascii_lowercase() {
+            'k' => (
+                CastlingSide::Kingside,
+                on_back_rank(PieceType::Rook).filter(|x| *x > king).max(),
+            ),
+            'q' => (
+                CastlingSide::Queenside,
+                on_back_rank(PieceType::Rook).filter(|x| *x < king).min(),
+            ),
+            file @ 'a'..='p' => {
+                let x = file as u8 - b'a';
+                let side = if x > king {
+                    CastlingSide::Kingside
+                } else {
+                    CastlingSide::Queenside
+                };
+                (side, on_back_rank(PieceType::Rook).find(|rook| *rook == x))
+            }
+            _ => return Err(invalid()),
+        };
+        let right = rights.rook_file_mut(color, side);
+        // Each right may only be given once
+        if right.is_some() {
+            return Err(invalid());
+        }
+        *right = Some(rook.ok_or_else(no_rook)?);
+    }
+    Ok(rights)
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::*;
+
+    #[test]
+    fn test_round_trip() {
+        let fens = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ];
+        for fen in fens.iter() {
+            assert_eq!(Position::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+        assert_eq!(Position::default().to_fen(), START_FEN);
+        // X-FEN names a rook's file when another rook stands further out
+        let fen = "1r2k1r1/8/8/8/8/8/8/R3K1RR w Gk - 0 1";
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(position.to_fen(), fen);
+        assert_eq!(position.castling_rights().white_kingside, Some(6));
+        assert_eq!(
+            position.to_shredder_fen(),
+            "1r2k1r1/8/8/8/8/8/8/R3K1RR w Gg - 0 1"
+        );
+        assert_eq!(
+            Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1")
+                .unwrap()
+                .to_fen(),
+            START_FEN
+        );
+        // The clocks may be left out
+        assert_eq!(
+            Position::from_fen("8/8/8/8/8/8/8/K6k w - -")
+                .unwrap()
+                .to_fen(),
+            "8/8/8/8/8/8/8/K6k w - - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_squares() {
+        let position = Position::from_fen(START_FEN).unwrap();
+        assert_eq!(
+            position.piece_at(Square { x: 4, y: 7 }),
+            Some(Piece {
+                kind: PieceType::King,
+                color: PieceColor::White,
+                square: Square { x: 4, y: 7 },
+            })
+        );
+        let position =
+            Position::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2")
+                .unwrap();
+        assert_eq!(position.en_passant_square(), Some(Square { x: 4, y: 2 }));
+    }
+
+    #[test]
+    fn test_errors() {
+        struct Test {
+            fen: &'static str,
+            error: FenError,
+        }
+        let tests = vec![
+            Test {
+                fen: "8/8/8/8/8/8/8/8 w",
+                error: FenError::WrongFieldCount(2),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8 w - - 0 1",
+                error: FenError::WrongRankCount(7),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K7k w - - 0 1",
+                error: FenError::WrongRankLength(1),
+            },
+            Test {
+                fen: "7/8/8/8/8/8/8/K6k w - - 0 1",
+                error: FenError::WrongRankLength(8),
+            },
+            // Wider boards are fine, as long as every rank agrees
+            Test {
+                fen: "10/10/9/10/10/10/10/K8k w - - 0 1",
+                error: FenError::WrongRankLength(6),
+            },
+            Test {
+                fen: "17/8/8/8/8/8/8/K6k w - - 0 1",
+                error: FenError::WrongRankLength(8),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K5xk w - - 0 1",
+                error: FenError::InvalidPiece('x'),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/8 w - - 0 1",
+                error: FenError::MissingKing(PieceColor::White),
+            },
+            Test {
+                fen: "K7/8/8/8/8/8/8/K6k w - - 0 1",
+                error: FenError::ExtraKing(PieceColor::White),
+            },
+            Test {
+                fen: "k7/8/8/8/8/8/8/K6P w - - 0 1",
+                error: FenError::PawnOnBackRank(Square { x: 7, y: 7 }),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k white - - 0 1",
+                error: FenError::InvalidSideToMove("white".to_string()),
+            },
+            Test {
+                fen: "4k3/8/8/8/8/8/8/4K2R w KK - 0 1",
+                error: FenError::InvalidCastlingRights("KK".to_string()),
+            },
+            Test {
+                fen: "4k3/8/8/8/8/8/8/4K2R w Kx - 0 1",
+                error: FenError::InvalidCastlingRights("Kx".to_string()),
+            },
+            // No rook to castle with
+            Test {
+                fen: "4k3/8/8/8/8/8/8/4K2R w KQ - 0 1",
+                error: FenError::NoCastlingRook('Q'),
+            },
+            Test {
+                fen: "4k3/8/8/8/8/8/8/R3K3 w B - 0 1",
+                error: FenError::NoCastlingRook('B'),
+            },
+            // Nor a king on the back rank
+            Test {
+                fen: "4k3/8/8/8/8/8/4K3/R6R w Q - 0 1",
+                error: FenError::NoCastlingRook('Q'),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k[Kq] w - - 0 1",
+                error: FenError::InvalidPocket("[Kq]".to_string()),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k[q w - - 0 1",
+                error: FenError::InvalidPocket("[q".to_string()),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k[PPPPPPPPPPPPPPPPP] w - - 0 1",
+                error: FenError::InvalidPocket("[PPPPPPPPPPPPPPPPP]".to_string()),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k w - e4 0 1",
+                error: FenError::InvalidEnPassantSquare("e4".to_string()),
+            },
+            // No black pawn passed over e3, and it is White to move
+            Test {
+                fen: "4k3/8/8/8/8/8/3PP3/4K3 w - e3 0 1",
+                error: FenError::InvalidEnPassantSquare("e3".to_string()),
+            },
+            Test {
+                fen: "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+                error: FenError::InvalidEnPassantSquare("e6".to_string()),
+            },
+            // A file wider boards have, but not this one
+            Test {
+                fen: "4k3/8/8/8/8/8/8/4K3 w - p6 0 1",
+                error: FenError::InvalidEnPassantSquare("p6".to_string()),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k w - - -1 1",
+                error: FenError::InvalidHalfmoveClock("-1".to_string()),
+            },
+            Test {
+                fen: "8/8/8/8/8/8/8/K6k w - - 0 0",
+                error: FenError::InvalidFullmoveNumber("0".to_string()),
+            },
+        ];
+        for test in tests {
+            assert_eq!(Position::from_fen(test.fen), Err(test.error));
+        }
+    }
+}
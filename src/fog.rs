@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use chess::bitboard::*;
+use chess::game::*;
+use chess::piece::*;
+
+/// Dark chess, or fog of war: each player sees only their own pieces and the
+/// squares those could move to. The players share the screen, which is handed
+/// over between turns so that neither sees the other's view.
+#[derive(Default)]
+pub struct FogOfWar {
+    /// The side whose view is on screen, or none while the screen is being
+    /// handed over
+    pub viewer: Option<PieceColor>,
+}
+
+impl FogOfWar {
+    /// The squares on show, or none for the whole board once the game is over
+    pub fn visible_squares(&self, game: &Game) -> Option<Bitboard> {
+        if game.is_over() {
+            return None;
+        }
+        Some(match self.viewer {
+            Some(color) => game.position().visible_squares(color),
+            None => Bitboard::EMPTY,
+        })
+    }
+
+    pub fn is_handing_over(&self, game: &Game) -> bool {
+        self.viewer.is_none() && !game.is_over()
+    }
+}
+
+/// Hides the view whenever the turn passes, until the next player takes over
+fn hand_over(game: Res<Game>, mut fog: ResMut<FogOfWar>) {
+    if game.is_changed() {
+        fog.viewer = None;
+    }
+}
+
+/// Hides the pieces on squares the viewer cannot see
+fn hide_pieces(
+    game: Res<Game>,
+    fog: Res<FogOfWar>,
+    pieces_query: Query<(&Piece, &Children)>,
+    mut visible_query: Query<&mut Visible>,
+) {
+    let visible_squares = fog.visible_squares(&game);
+    for (piece, children) in pieces_query.iter() {
+        let is_visible = visible_squares.map_or(true, |squares| squares.contains(piece.square));
+        for child in children.iter() {
+            if let Ok(mut visible) = visible_query.get_mut(*child) {
+                visible.is_visible = is_visible;
+            }
+        }
+    }
+}
+
+pub struct FogOfWarPlugin;
+
+impl Plugin for FogOfWarPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<FogOfWar>()
+            .add_system(hand_over.system())
+            .add_system(hide_pieces.system());
+    }
+}
@@ -0,0 +1,334 @@
+use std::cmp::min;
+
+use crate::moves::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::tree::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Win(PieceColor),
+    Draw,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EndReason {
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    InsufficientMaterial,
+    KingOfTheHill,
+    ThreeChecks,
+    AllPiecesLost,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GameResult {
+    pub outcome: Outcome,
+    pub reason: EndReason,
+}
+
+impl GameResult {
+    /// The result as written on a score sheet
+    pub fn score(&self) -> &'static str {
+        match self.outcome {
+            Outcome::Win(PieceColor::White) => "1-0",
+            Outcome::Win(PieceColor::Black) => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self.reason {
+            EndReason::Checkmate => "Checkmate",
+            EndReason::Stalemate => "Stalemate",
+            EndReason::FiftyMoveRule => "Fifty-move rule",
+            EndReason::SeventyFiveMoveRule => "Seventy-five-move rule",
+            EndReason::ThreefoldRepetition => "Threefold repetition",
+            EndReason::FivefoldRepetition => "Fivefold repetition",
+            EndReason::InsufficientMaterial => "Insufficient material",
+            EndReason::KingOfTheHill => "King reached the centre",
+            EndReason::ThreeChecks => "Third check",
+            EndReason::AllPiecesLost => "All pieces lost",
+        }
+    }
+}
+
+/// A game played from some starting position: the current position, the
+/// moves that led to it, and how the game ended. Moves can be taken back and
+/// replayed, and every line tried is kept in the game tree.
+#[derive(Clone, Debug)]
+pub struct Game {
+    tree: GameTree,
+    /// The node of the tree for the current position
+    node: NodeId,
+    position: Position,
+    /// The moves made since the start, latest last
+    played: Vec<Undo>,
+    /// The moves taken back, to be replayed with `redo`, next one last
+    undone: Vec<Move>,
+    /// The hashes of the start position and of the position after each move
+    hashes: Vec<u64>,
+    result: Option<GameResult>,
+    /// A draw the player to move may claim, but which does not end the game
+    /// on its own
+    claimable_draw: Option<EndReason>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(Position::default())
+    }
+}
+
+impl Game {
+    pub fn new(position: Position) -> Game {
+        Game::from_tree(GameTree::new(position))
+    }
+
+    /// The game at the end of the main line of the tree
+    pub fn from_tree(tree: GameTree) -> Game {
+        let position = *tree.start();
+        let main_line = tree.main_line();
+        let mut game = Game {
+            tree,
+            node: GameTree::ROOT,
+            position,
+            played: Vec::new(),
+            undone: Vec::new(),
+            hashes: vec![position.hash()],
+            result: None,
+            claimable_draw: None,
+        };
+        game.update_status();
+        for mv in main_line {
+            if game.make_move(mv).is_err() {
+                break;
+            }
+        }
+        game
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    /// The hashes of the start position and of the position after each move
+    /// played since, the current position's last
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// The position the game started from
+    pub fn start(&self) -> &Position {
+        self.tree.start()
+    }
+
+    /// Every line tried in the game, with any annotations it was loaded with
+    pub fn tree(&self) -> &GameTree {
+        &self.tree
+    }
+
+    /// The node of the tree for the current position
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    /// The moves known to follow the current position, the main line first
+    pub fn lines(&self) -> Vec<Move> {
+        self.tree
+            .node(self.node)
+            .children()
+            .iter()
+            .filter_map(|child| self.tree.node(*child).mv())
+            .collect()
+    }
+
+    /// The moves made since the start
+    pub fn moves(&self) -> Vec<Move> {
+        self.played.iter().map(|undo| undo.mv).collect()
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    pub fn claimable_draw(&self) -> Option<EndReason> {
+        self.claimable_draw
+    }
+
+    pub fn claim_draw(&mut self) {
+        if let Some(reason) = self.claimable_draw.take() {
+            self.draw(reason);
+        }
+    }
+
+    pub fn make_move(&mut self, mv: Move) -> Result<(), IllegalMove> {
+        if self.is_over() {
+            return Err(IllegalMove::GameOver);
+        }
+        let undo = self.position.make_move(mv)?;
+        self.played.push(undo);
+        self.hashes.push(self.position.hash());
+        self.node = self.tree.add_move(self.node, mv);
+        // Playing the move that was taken back keeps the rest of the line
+        if self.undone.last() == Some(&mv) {
+            self.undone.pop();
+        } else {
+            self.undone.clear();
+        }
+        self.update_status();
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.played.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.lines().is_empty()
+    }
+
+    /// Takes back the last move, including one that ended the game, and
+    /// returns it
+    pub fn undo(&mut self) -> Option<Move> {
+        let undo = self.played.pop()?;
+        self.position.unmake_move(undo);
+        self.hashes.pop();
+        self.undone.push(undo.mv);
+        self.node = self.tree.node(self.node).parent().unwrap_or(GameTree::ROOT);
+        self.result = None;
+        self.update_status();
+        Some(undo.mv)
+    }
+
+    /// Replays the last move taken back, or else the main line, and returns
+    /// the move
+    pub fn redo(&mut self) -> Option<Move> {
+        let lines = self.lines();
+        let mv = match self.undone.last() {
+            Some(mv) if lines.contains(mv) => *mv,
+            _ => *lines.first()?,
+        };
+        self.make_move(mv).ok()?;
+        Some(mv)
+    }
+
+    fn draw(&mut self, reason: EndReason) {
+        self.result = Some(GameResult {
+            outcome: Outcome::Draw,
+            reason,
+        });
+    }
+
+    /// Looks for the end of the game after a move
+    fn update_status(&mut self) {
+        let hash = self.position.hash();
+        // Positions from before a capture or pawn move can never come back
+        let reversible = min(
+            self.position.halfmove_clock() as usize + 1,
+            self.hashes.len(),
+        );
+        let repetitions = self.hashes[self.hashes.len() - reversible..]
+            .iter()
+            .filter(|h| **h == hash)
+            .count();
+        self.claimable_draw = None;
+
+        let variant = self.position.variant();
+        if let Some(result) = variant.result(&self.position) {
+            self.result = Some(result);
+        } else if !self.position.has_valid_move() {
+            self.result = Some(variant.no_moves_result(&self.position));
+        } else if repetitions >= 5 {
+            self.draw(EndReason::FivefoldRepetition);
+        } else if self.position.halfmove_clock() >= 150 {
+            self.draw(EndReason::SeventyFiveMoveRule);
+        } else if variant.is_insufficient_material(&self.position) {
+            self.draw(EndReason::InsufficientMaterial);
+        } else if repetitions >= 3 {
+            self.claimable_draw = Some(EndReason::ThreefoldRepetition);
+        } else if self.position.halfmove_clock() >= 100 {
+            self.claimable_draw = Some(EndReason::FiftyMoveRule);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::*;
+    use crate::square::*;
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut game = Game::default();
+        let knight_moves = [
+            (Square { x: 6, y: 7 }, Square { x: 5, y: 5 }),
+            (Square { x: 6, y: 0 }, Square { x: 5, y: 2 }),
+            (Square { x: 5, y: 5 }, Square { x: 6, y: 7 }),
+            (Square { x: 5, y: 2 }, Square { x: 6, y: 0 }),
+        ];
+        let find_move = |game: &Game, (from, to)| game.position().find_move(from, to, None);
+        for squares in knight_moves.iter().chain(knight_moves.iter()) {
+            assert_eq!(game.claimable_draw(), None);
+            game.make_move(find_move(&game, *squares).unwrap()).unwrap();
+        }
+        assert_eq!(game.claimable_draw(), Some(EndReason::ThreefoldRepetition));
+        game.claim_draw();
+        assert_eq!(
+            game.result().map(|result| result.reason),
+            Some(EndReason::ThreefoldRepetition)
+        );
+        let mv = find_move(&game, knight_moves[0]).unwrap();
+        assert!(game.make_move(mv).is_err());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::default();
+        let play = |game: &mut Game, from, to| {
+            let mv = game.position().find_move(from, to, None).unwrap();
+            game.make_move(mv).unwrap();
+        };
+        // Fool's mate
+        play(&mut game, Square { x: 5, y: 6 }, Square { x: 5, y: 5 });
+        play(&mut game, Square { x: 4, y: 1 }, Square { x: 4, y: 3 });
+        play(&mut game, Square { x: 6, y: 6 }, Square { x: 6, y: 4 });
+        play(&mut game, Square { x: 3, y: 0 }, Square { x: 7, y: 4 });
+        assert_eq!(
+            game.result().map(|result| result.reason),
+            Some(EndReason::Checkmate)
+        );
+        let checkmate = *game.position();
+
+        assert!(game.undo().is_some());
+        assert!(!game.is_over());
+        while game.undo().is_some() {}
+        assert_eq!(*game.position(), Position::default());
+        assert!(!game.can_undo());
+
+        while game.redo().is_some() {}
+        assert_eq!(*game.position(), checkmate);
+        assert!(game.is_over());
+
+        // A different move starts a side line, and the line taken back is
+        // still there to step into
+        game.undo();
+        game.undo();
+        play(&mut game, Square { x: 7, y: 6 }, Square { x: 7, y: 5 });
+        assert!(!game.can_redo());
+        assert_eq!(game.moves().len(), 3);
+        game.undo();
+        assert_eq!(game.lines().len(), 2);
+        game.redo();
+        assert_eq!(game.moves().len(), 3, "redo follows the line taken back");
+        assert_eq!(game.tree().main_line().len(), 4);
+    }
+}
@@ -0,0 +1,24 @@
+//! The rules of chess, without anything to do with displaying the game. The
+//! Bevy app in `main.rs` is one user of this library; bots, tests and tools can
+//! depend on it alone.
+
+pub mod attacks;
+pub mod bitboard;
+pub mod chess960;
+pub mod crazyhouse;
+pub mod evaluation;
+pub mod fen;
+pub mod game;
+pub mod limits;
+pub mod moves;
+pub mod perft;
+pub mod pgn;
+pub mod piece;
+pub mod position;
+pub mod san;
+pub mod search;
+pub mod square;
+pub mod tree;
+pub mod uci;
+pub mod variant;
+pub mod zobrist;
@@ -0,0 +1,248 @@
+//! When a search has to stop: after a fixed depth, a fixed number of
+//! positions or a fixed time, or, playing on a clock, once it has used its
+//! share of the time left. The share is the time left spread over the moves
+//! still to play before the next time control, or over a typical number of
+//! moves without one, plus most of the increment.
+
+use std::cmp::{max, min};
+use std::time::{Duration, Instant};
+
+use crate::piece::*;
+
+/// How many moves a game on a clock is expected to go on for, when the time
+/// control does not say
+const EXPECTED_MOVES_TO_GO: u32 = 30;
+
+/// The time kept back on the clock for the move to get played once found
+pub const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// What a search may use. Without any limit it goes on until stopped.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Limits {
+    /// The most moves ahead to look
+    pub depth: Option<u32>,
+    /// The most positions to visit
+    pub nodes: Option<u64>,
+    /// The time to think, however much is left on the clock
+    pub movetime: Option<Duration>,
+    /// The time left on each side's clock, indexed by `PieceColor as usize`
+    pub time: [Option<Duration>; 2],
+    /// The time added to each side's clock after each of its moves
+    pub increment: [Duration; 2],
+    /// The moves to play before the next time control
+    pub moves_to_go: Option<u32>,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> Limits {
+        Limits {
+            nodes: Some(nodes),
+            ..Limits::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> Limits {
+        Limits {
+            movetime: Some(movetime),
+            ..Limits::default()
+        }
+    }
+}
+
+/// Keeps track of how long the side to move has been thinking
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    /// How long to think for in the normal run of things on a clock. No deeper
+    /// search is started past half of it, as it would hardly ever finish in
+    /// time. A fixed time to think has none, as all of it is there to be used.
+    optimum: Option<Duration>,
+    /// How long the search may go on for at the most before it is aborted
+    maximum: Option<Duration>,
+}
+
+impl TimeManager {
+    /// Starts the clock on thinking about a move for the side
+    pub fn new(limits: &Limits, color: PieceColor) -> TimeManager {
+        let (optimum, maximum) = match (limits.movetime, limits.time[color as usize]) {
+            (Some(movetime), _) => (None, Some(movetime)),
+            (None, Some(time_left)) => {
+                let (optimum, maximum) = allocate_time(
+                    time_left,
+                    limits.increment[color as usize],
+                    limits.moves_to_go,
+                );
+                (Some(optimum), Some(maximum))
+            }
+            (None, None) => (None, None),
+        };
+        TimeManager {
+            start: Instant::now(),
+            optimum,
+            maximum,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Whether there is time to look another move deeper
+    pub fn can_deepen(&self) -> bool {
+        match self.optimum {
+            Some(optimum) => self.elapsed() < optimum / 2,
+            None => !self.is_out_of_time(),
+        }
+    }
+
+    /// Whether the search has to stop at once
+    pub fn is_out_of_time(&self) -> bool {
+        match self.maximum {
+            Some(maximum) => self.elapsed() >= maximum,
+            None => false,
+        }
+    }
+}
+
+/// The usual and the longest time to think about a move with that much time
+/// left on the clock
+fn allocate_time(
+    time_left: Duration,
+    increment: Duration,
+    moves_to_go: Option<u32>,
+) -> (Duration, Duration) {
+    let usable = time_left.saturating_sub(MOVE_OVERHEAD);
+    let moves_to_go = max(moves_to_go.unwrap_or(EXPECTED_MOVES_TO_GO), 1);
+    let optimum = min(usable / moves_to_go + increment * 3 / 4, usable);
+    // A move that needs longer may take from the moves after it, but never
+    // more than half of what is left
+    let maximum = max(min(optimum * 3, usable / 2), optimum);
+    (optimum, maximum)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::limits::*;
+
+    /// A time manager that has been thinking for a while already
+    fn started(limits: &Limits, color: PieceColor, elapsed: Duration) -> TimeManager {
+        TimeManager {
+            start: Instant::now() - elapsed,
+            ..TimeManager::new(limits, color)
+        }
+    }
+
+    #[test]
+    fn test_allocate_time() {
+        struct Test {
+            time_left: u64,
+            increment: u64,
+            moves_to_go: Option<u32>,
+            optimum: u64,
+            maximum: u64,
+        }
+        let tests = vec![
+            // A thirtieth of the time left
+            Test {
+                time_left: 60_050,
+                increment: 0,
+                moves_to_go: None,
+                optimum: 2_000,
+                maximum: 6_000,
+            },
+            // And most of the increment on top
+            Test {
+                time_left: 60_050,
+                increment: 2_000,
+                moves_to_go: None,
+                optimum: 3_500,
+                maximum: 10_500,
+            },
+            Test {
+                time_left: 10_050,
+                increment: 0,
+                moves_to_go: Some(2),
+                optimum: 5_000,
+                maximum: 5_000,
+            },
+            // The last move before the time control may use all of it
+            Test {
+                time_left: 10_050,
+                increment: 0,
+                moves_to_go: Some(1),
+                optimum: 10_000,
+                maximum: 10_000,
+            },
+            // Never more than is left, even with an increment
+            Test {
+                time_left: 1_050,
+                increment: 5_000,
+                moves_to_go: None,
+                optimum: 1_000,
+                maximum: 1_000,
+            },
+            Test {
+                time_left: 20,
+                increment: 0,
+                moves_to_go: None,
+                optimum: 0,
+                maximum: 0,
+            },
+        ];
+        for test in tests {
+            let ms = Duration::from_millis;
+            let (optimum, maximum) =
+                allocate_time(ms(test.time_left), ms(test.increment), test.moves_to_go);
+            assert_eq!(optimum, ms(test.optimum), "{}ms left", test.time_left);
+            assert_eq!(maximum, ms(test.maximum), "{}ms left", test.time_left);
+        }
+    }
+
+    #[test]
+    fn test_time_manager() {
+        let mut limits = Limits::default();
+        let time_manager = TimeManager::new(&limits, PieceColor::White);
+        assert!(time_manager.can_deepen());
+        assert!(!time_manager.is_out_of_time());
+
+        // Only the clock of the side to move counts
+        limits.time[PieceColor::Black as usize] = Some(Duration::ZERO);
+        let time_manager = TimeManager::new(&limits, PieceColor::White);
+        assert!(!time_manager.is_out_of_time());
+        let time_manager = TimeManager::new(&limits, PieceColor::Black);
+        assert!(!time_manager.can_deepen());
+        assert!(time_manager.is_out_of_time());
+
+        // A fixed time to think ignores the clock
+        limits.movetime = Some(Duration::from_secs(60));
+        let time_manager = TimeManager::new(&limits, PieceColor::Black);
+        assert!(time_manager.can_deepen());
+        assert!(!time_manager.is_out_of_time());
+
+        // Past half of its time, a search on the clock stops deepening, but one
+        // with a fixed time to think goes on until it is all used
+        let mut on_clock = Limits::default();
+        on_clock.time[PieceColor::White as usize] = Some(Duration::from_millis(1_050));
+        on_clock.moves_to_go = Some(1);
+        let fixed = Limits::movetime(Duration::from_millis(1_000));
+        let after = |limits: &Limits, elapsed: u64| {
+            started(limits, PieceColor::White, Duration::from_millis(elapsed))
+        };
+        assert!(after(&on_clock, 100).can_deepen());
+        assert!(after(&fixed, 100).can_deepen());
+        assert!(!after(&on_clock, 600).can_deepen());
+        assert!(!after(&on_clock, 600).is_out_of_time());
+        assert!(after(&fixed, 600).can_deepen());
+        assert!(!after(&fixed, 600).is_out_of_time());
+        assert!(after(&on_clock, 1_000).is_out_of_time());
+        assert!(!after(&fixed, 1_000).can_deepen());
+        assert!(after(&fixed, 1_000).is_out_of_time());
+    }
+}
@@ -1,18 +1,49 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use bevy_mod_picking::*;
 
+use chess::chess960::*;
+use chess::game::*;
+use chess::limits::*;
+use chess::pgn::*;
+use chess::piece::*;
+use chess::position::*;
+use chess::variant::*;
+
+mod ai;
+use ai::*;
 mod board;
 use board::*;
+mod fog;
+use fog::*;
 mod pieces;
 use pieces::*;
+mod save;
+use save::*;
+mod ui;
+use ui::*;
 
 fn main() {
-    App::build()
+    let (game, computer) = match starting_game().and_then(|game| Ok((game, computer_player()?))) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let title = match game.start().variant() {
+        Variant::Standard => "Chess!".to_string(),
+        variant => format!("Chess! ({})", variant),
+    };
+
+    let mut app = App::build();
+    app.insert_resource(game)
         // Set antialiasing to use 4 samples
         .insert_resource(Msaa { samples: 4 })
         // Set WindowDescriptor Resource to change title and size
         .insert_resource(WindowDescriptor {
-            title: "Chess!".to_string(),
+            title,
             width: 1000.,
             height: 1000.,
             ..Default::default()
@@ -23,17 +54,150 @@ fn main() {
         .add_plugin(InteractablePickingPlugin)
         .add_plugin(BoardPlugin)
         .add_plugin(PiecesPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(UiPlugin)
         .add_startup_system(setup.system())
-        .run();
+        .add_system(print_fen.system());
+    // Dark chess, played by two players sharing the screen
+    if std::env::args().any(|arg| arg == "--fog") {
+        app.add_plugin(FogOfWarPlugin);
+    }
+    if let Some(computer) = computer {
+        app.insert_resource(computer)
+            .add_plugin(ComputerPlayerPlugin);
+    }
+    app.run();
+}
+
+/// The value given on the command line after the option, which is empty when
+/// the option comes last
+fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == option)
+        .map(|index| args.get(index + 1).map(String::as_str).unwrap_or(""))
+}
+
+/// The game to play: from the standard starting position, from the one given
+/// on the command line with `--fen <FEN>`, from the numbered Chess960 position
+/// given with `--chess960 [index]` (a random one without the number), or the
+/// first game in the file given with `--pgn <file>`, ready to be continued.
+/// `--variant <name>` plays by the rules of a variant, except for PGN games,
+/// which keep their own.
+fn starting_game() -> Result<Game, String> {
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |option| option_value(&args, option);
+    let variant = match value_of("--variant") {
+        Some(name) => name.parse().map_err(|error| {
+            let names: Vec<String> = VARIANTS.iter().map(Variant::to_string).collect();
+            format!("{}, choose from: {}", error, names.join(", "))
+        })?,
+        None => Variant::Standard,
+    };
+    if let Some(fen) = value_of("--fen") {
+        let position = Position::from_variant_fen(fen, variant)
+            .map_err(|error| format!("Invalid FEN: {}", error))?;
+        return Ok(Game::new(position));
+    }
+    if let Some(index) = value_of("--chess960") {
+        let index = if index.is_empty() || index.starts_with("--") {
+            random_chess960_index()
+        } else {
+            index
+                .parse()
+                .map_err(|_| format!("Invalid Chess960 position: {}", index))?
+        };
+        return Position::chess960(index)
+            .map(|position| Game::new(position.with_variant(variant)))
+            .ok_or_else(|| {
+                format!(
+                    "Chess960 positions are numbered 0 to {}",
+                    CHESS960_POSITIONS - 1
+                )
+            });
+    }
+    if let Some(path) = value_of("--pgn") {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Could not read {}: {}", path, error))?;
+        let games = parse_pgn(&text).map_err(|error| format!("Invalid PGN: {}", error))?;
+        return match games.first() {
+            Some(game) => Ok(game.to_game()),
+            None => Err(format!("No game in {}", path)),
+        };
+    }
+    Ok(Game::new(variant.start_position()))
+}
+
+/// The computer opponent asked for with `--computer [white|black|both]`,
+/// playing Black without a side given. It thinks for a second a move, or looks
+/// as many moves ahead as given with `--depth <n>`, visits as many positions
+/// as given with `--nodes <n>` or thinks for as long as given with
+/// `--movetime <milliseconds>`.
+fn computer_player() -> Result<Option<ComputerPlayer>, String> {
+    let args: Vec<String> = std::env::args().collect();
+    let colors = match option_value(&args, "--computer") {
+        Some("white") => vec![PieceColor::White],
+        Some("both") => vec![PieceColor::White, PieceColor::Black],
+        Some(side) if side == "black" || side.is_empty() || side.starts_with("--") => {
+            vec![PieceColor::Black]
+        }
+        Some(side) => {
+            return Err(format!(
+                "Invalid side for the computer: {}, choose from: white, black, both",
+                side
+            ))
+        }
+        None => return Ok(None),
+    };
+    let limit = |option: &str, name: &str| {
+        option_value(&args, option)
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|value| *value > 0)
+                    .ok_or_else(|| format!("Invalid {}: {}", name, value))
+            })
+            .transpose()
+    };
+    let mut limits = Limits {
+        depth: limit("--depth", "depth")?.map(|depth| depth as u32),
+        nodes: limit("--nodes", "number of positions")?,
+        movetime: limit("--movetime", "time to think")?.map(Duration::from_millis),
+        ..Limits::default()
+    };
+    if limits == Limits::default() {
+        limits.movetime = Some(Duration::from_secs(1));
+    }
+    Ok(Some(ComputerPlayer { colors, limits }))
+}
+
+/// A Chess960 position picked from the clock, which is random enough to set up
+/// a game
+fn random_chess960_index() -> u16 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    (nanos % CHESS960_POSITIONS as u32) as u16
+}
+
+/// Prints the current position as FEN when F is pressed, to copy it into other
+/// tools or back into `--fen`. In fog of war that would give the whole board
+/// away, so it waits until the game is over.
+fn print_fen(keyboard_input: Res<Input<KeyCode>>, game: Res<Game>, fog: Option<Res<FogOfWar>>) {
+    if keyboard_input.just_pressed(KeyCode::F) && (fog.is_none() || game.is_over()) {
+        println!("{}", game.position().to_fen());
+    }
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, game: Res<Game>) {
+    // Look at the middle of the board, however wide it is
+    let centre = (game.position().files() - 1) as f32 / 2.;
     commands
         // Camera
         .spawn_bundle(PerspectiveCameraBundle {
             transform: Transform::from_matrix(Mat4::from_rotation_translation(
                 Quat::from_axis_angle(Vec3::new(1.0, 0., 0.), -1.05),
-                Vec3::new(3.5, 10.0, 10.0),
+                Vec3::new(centre, 10.0, 10.0),
             )),
             ..Default::default()
         })
@@ -41,7 +205,7 @@ fn setup(mut commands: Commands) {
         // Light
         .commands()
         .spawn_bundle(LightBundle {
-            transform: Transform::from_translation(Vec3::new(4.0, 8.0, 4.0)),
+            transform: Transform::from_translation(Vec3::new(centre + 0.5, 8.0, 4.0)),
             ..Default::default()
         });
 }
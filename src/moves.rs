@@ -0,0 +1,77 @@
+use std::fmt;
+
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MoveKind {
+    Normal,
+    /// A pawn advancing two squares from its starting rank
+    DoublePawnPush,
+    EnPassant,
+    Castling(CastlingSide),
+    Promotion(PieceType),
+    /// A piece from the pocket put on the board in Crazyhouse
+    Drop(PieceType),
+}
+
+/// A move of the piece on `from` to `to`. Castling is stored as the king
+/// moving onto its own rook, which stays unambiguous in Chess960 where the
+/// king may move a single square or not at all. A drop has no starting
+/// square, so `from` is the same as `to`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub kind: MoveKind,
+}
+
+impl Move {
+    pub fn promotion(&self) -> Option<PieceType> {
+        match self.kind {
+            MoveKind::Promotion(kind) => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// The move in UCI notation on a board with that many files. Castling
+    /// with the king and rooks on their classical files, the king in the
+    /// middle and the rooks in the corners, is written as the king's move, so
+    /// `f1i1` on ten files.
+    pub fn to_uci(&self, files: u8) -> String {
+        let mut uci = String::new();
+        // Writing to a string cannot fail
+        let _ = self.write_uci(&mut uci, files);
+        uci
+    }
+
+    fn write_uci(&self, f: &mut impl fmt::Write, files: u8) -> fmt::Result {
+        if let MoveKind::Drop(kind) = self.kind {
+            return write!(f, "{}@{}", kind.letter(), self.to);
+        }
+        let to = match self.kind {
+            // Castling with the rooks on their classical files is written as
+            // the king's move, otherwise as the king taking its own rook
+            MoveKind::Castling(side)
+                if self.from.x == files / 2 && (self.to.x == 0 || self.to.x == files - 1) =>
+            {
+                castling_squares(side, self.from.y, files).0
+            }
+            _ => self.to,
+        };
+        write!(f, "{}{}", self.from, to)?;
+        if let Some(kind) = self.promotion() {
+            write!(f, "{}", kind.letter().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// The move in UCI notation on a board of eight files, like `e2e4`, `e7e8q`
+/// for a promotion or `N@f3` for a drop. Wider boards need `to_uci`.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_uci(f, 8)
+    }
+}
@@ -0,0 +1,177 @@
+//! Perft counts the leaf nodes of the legal move tree to a fixed depth. The
+//! counts for well-known positions are published, so comparing against them
+//! checks move generation as a whole: castling, en passant, promotions, pins
+//! and checks all change the numbers.
+
+use crate::moves::*;
+use crate::position::*;
+
+/// The number of move sequences of exactly `depth` moves from the position
+pub fn perft(position: &Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = position.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .into_iter()
+        .map(|mv| {
+            let mut next = *position;
+            next.make_move_unchecked(mv);
+            perft(&next, depth - 1)
+        })
+        .sum()
+}
+
+/// The perft count below each legal move, for narrowing down which move a
+/// wrong total comes from
+pub fn divide(position: &Position, depth: u32) -> Vec<(Move, u64)> {
+    position
+        .legal_moves()
+        .into_iter()
+        .map(|mv| {
+            let mut next = *position;
+            next.make_move_unchecked(mv);
+            (mv, perft(&next, depth.saturating_sub(1)))
+        })
+        .collect()
+}
+
+/// Lays out the counts from `divide` one move per line, like other engines do
+/// (`e2e4: 20`), followed by the total
+pub fn format_divide(divide: &[(Move, u64)]) -> String {
+    let mut output = String::new();
+    for (mv, count) in divide {
+        output += &format!("{}: {}\n", mv, count);
+    }
+    let total: u64 = divide.iter().map(|(_, count)| count).sum();
+    output += &format!("\nNodes searched: {}\n", total);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::*;
+    use crate::perft::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const PROMOTIONS: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+
+    #[test]
+    fn test_perft() {
+        struct Test {
+            name: &'static str,
+            fen: &'static str,
+            depth: u32,
+            nodes: u64,
+        }
+        let tests = vec![
+            Test {
+                name: "start position",
+                fen: START_FEN,
+                depth: 4,
+                nodes: 197281,
+            },
+            Test {
+                name: "Kiwipete",
+                fen: KIWIPETE,
+                depth: 3,
+                nodes: 97862,
+            },
+            // Discovered checks along the rank after en passant captures
+            Test {
+                name: "en passant pins",
+                fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                depth: 4,
+                nodes: 43238,
+            },
+            Test {
+                name: "en passant capture gives check",
+                fen: "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+                depth: 6,
+                nodes: 1440467,
+            },
+            Test {
+                name: "castling gives check",
+                fen: "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+                depth: 6,
+                nodes: 661072,
+            },
+            // Promotions to every piece, with and without capturing
+            Test {
+                name: "promotions",
+                fen: PROMOTIONS,
+                depth: 3,
+                nodes: 9467,
+            },
+            // Chess960, with the king castling queenside from g1 to c1
+            Test {
+                name: "Chess960 castling both ways",
+                fen: "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                depth: 3,
+                nodes: 12189,
+            },
+            Test {
+                name: "Chess960 king beside its rook",
+                fen: "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                depth: 3,
+                nodes: 18002,
+            },
+            Test {
+                name: "promotion gives check",
+                fen: "4k3/1P6/8/8/8/8/K7/8 w - - 0 1",
+                depth: 6,
+                nodes: 217342,
+            },
+            // Ten files, with archbishops and chancellors
+            Test {
+                name: "Capablanca start position",
+                fen: CAPABLANCA_FEN,
+                depth: 3,
+                nodes: 25228,
+            },
+        ];
+        for test in tests {
+            let position = Position::from_fen(test.fen).unwrap();
+            let nodes = perft(&position, test.depth);
+            assert!(
+                nodes == test.nodes,
+                "{} at depth {}: {} nodes instead of {}\n{}",
+                test.name,
+                test.depth,
+                nodes,
+                test.nodes,
+                format_divide(&divide(&position, test.depth))
+            );
+        }
+    }
+
+    #[test]
+    fn test_divide() {
+        let divide = divide(&Position::default(), 3);
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        assert!(format_divide(&divide).contains("e2e4: 600\n"));
+    }
+
+    #[test]
+    fn test_unmake_move() {
+        fn check(position: &mut Position, depth: u32) {
+            if depth == 0 {
+                return;
+            }
+            let before = *position;
+            for mv in position.legal_moves() {
+                let undo = position.make_move_unchecked(mv);
+                check(position, depth - 1);
+                position.unmake_move(undo);
+                assert_eq!(*position, before, "after taking back {}", mv);
+            }
+        }
+        for fen in [KIWIPETE, PROMOTIONS] {
+            check(&mut Position::from_fen(fen).unwrap(), 3);
+        }
+    }
+}
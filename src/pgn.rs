@@ -0,0 +1,668 @@
+//! Portable Game Notation, the usual format for storing games: tag pairs like
+//! `[White "Morphy"]`, then the moves in SAN with move numbers, ending with the
+//! result. Moves may be followed by `{comments}`, NAGs like `$1` and side
+//! lines in parentheses, which can nest.
+
+use std::fmt;
+
+use crate::fen::*;
+use crate::game::*;
+use crate::moves::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::san::*;
+use crate::tree::*;
+use crate::variant::*;
+
+/// The tags every PGN game has, in the order they are written
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnError {
+    UnterminatedTag,
+    /// A tag that is not a name followed by a quoted value
+    InvalidTag(String),
+    UnterminatedComment,
+    UnbalancedVariation,
+    /// A variation before any move it could replace
+    MisplacedVariation,
+    InvalidFen(FenError),
+    /// A `Variant` tag naming rules that are not supported
+    UnsupportedVariant(String),
+    /// A move that cannot be played, with its number and the side playing it
+    InvalidMove {
+        number: u32,
+        color: PieceColor,
+        error: SanError,
+    },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::UnterminatedTag => write!(f, "a tag is missing its closing ']'"),
+            PgnError::InvalidTag(tag) => write!(f, "invalid tag [{}]", tag),
+            PgnError::UnterminatedComment => {
+                write!(f, "a comment is missing its closing '}}'")
+            }
+            PgnError::UnbalancedVariation => {
+                write!(f, "variation parentheses do not match up")
+            }
+            PgnError::MisplacedVariation => {
+                write!(f, "a variation must follow the move it replaces")
+            }
+            PgnError::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+            PgnError::UnsupportedVariant(name) => write!(f, "unsupported variant {}", name),
+            PgnError::InvalidMove {
+                number,
+                color,
+                error,
+            } => {
+                let dots = match color {
+                    PieceColor::White => ".",
+                    PieceColor::Black => "...",
+                };
+                write!(f, "move {}{}: {}", number, dots, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Tag(String, String),
+    San(String),
+    Result(String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+}
+
+fn is_result(symbol: &str) -> bool {
+    matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+fn parse_tag(text: &str) -> Result<Token, PgnError> {
+    let invalid = || PgnError::InvalidTag(text.to_string());
+    let text = text.trim();
+    let name_end = text.find(char::is_whitespace).ok_or_else(invalid)?;
+    let (name, value) = text.split_at(name_end);
+    let value = value.trim();
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(invalid());
+    }
+    let value = value[1..value.len() - 1]
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+    Ok(Token::Tag(name.to_string(), value))
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut at_line_start = true;
+    while let Some(c) = chars.next() {
+        let was_at_line_start = at_line_start;
+        at_line_start = c == '\n';
+        match c {
+            c if c.is_whitespace() => {}
+            // A line starting with % is an escape for other programs to use
+            '%' if was_at_line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        at_line_start = true;
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                let mut tag = String::new();
+                let mut in_quotes = false;
+                let mut escaped = false;
+                loop {
+                    let c = chars.next().ok_or(PgnError::UnterminatedTag)?;
+                    if c == ']' && !in_quotes {
+                        break;
+                    }
+                    if c == '"' && !escaped {
+                        in_quotes = !in_quotes;
+                    }
+                    escaped = c == '\\' && !escaped;
+                    tag.push(c);
+                }
+                tokens.push(parse_tag(&tag)?);
+            }
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            ';' => {
+                let mut comment = String::new();
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        at_line_start = true;
+                        break;
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            _ => {
+                let mut symbol = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[]{}();".contains(c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    chars.next();
+                }
+                tokens.extend(symbol_tokens(&symbol));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// The NAG for an annotation written after a move, like 5 for `!?`
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// Splits up a run of text between spaces, which may hold a move number, a
+/// move with annotations like `!?` stuck to it, a NAG or a result
+fn symbol_tokens(symbol: &str) -> Vec<Token> {
+    if is_result(symbol) {
+        return vec![Token::Result(symbol.to_string())];
+    }
+    if let Some(number) = symbol.strip_prefix('$') {
+        return number.parse().map(Token::Nag).into_iter().collect();
+    }
+    // Move numbers like `12.` or `12...`, which may be followed by the move
+    let symbol = if symbol.starts_with(|c: char| c.is_ascii_digit()) && symbol.contains('.') {
+        symbol.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+    } else {
+        symbol
+    };
+    let san = symbol.trim_end_matches(|c| c == '!' || c == '?');
+    let mut tokens = Vec::new();
+    if !san.is_empty() {
+        tokens.push(Token::San(san.to_string()));
+    }
+    tokens.extend(suffix_nag(&symbol[san.len()..]).map(Token::Nag));
+    tokens
+}
+
+/// Adds to a comment, or starts it
+fn append_comment(comment: &mut Option<String>, text: String) {
+    match comment {
+        Some(comment) => {
+            comment.push(' ');
+            *comment += &text;
+        }
+        None => *comment = Some(text),
+    }
+}
+
+/// A game read from or to be written to PGN
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnGame {
+    tags: Vec<(String, String)>,
+    tree: GameTree,
+}
+
+impl PgnGame {
+    /// The lines of the game, with the moves played to reach the current
+    /// position as the main line, and the Seven Tag Roster filled in with
+    /// unknown values except for the result
+    pub fn from_game(game: &Game) -> PgnGame {
+        let mut tree = game.tree().clone();
+        tree.promote(game.node());
+        let mut pgn = PgnGame {
+            tags: Vec::new(),
+            tree,
+        };
+        for name in SEVEN_TAG_ROSTER.iter() {
+            let value = match *name {
+                "Date" => "????.??.??",
+                "Result" => game.result().map_or("*", |result| result.score()),
+                _ => "?",
+            };
+            pgn.set_tag(name, value);
+        }
+        if *game.start() != Position::default() {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", &game.start().to_fen());
+        }
+        let variant = game.start().variant();
+        if variant != Variant::Standard {
+            pgn.set_tag("Variant", &variant.to_string());
+        } else if game.start().has_chess960_castling() {
+            pgn.set_tag("Variant", "Chess960");
+        }
+        pgn
+    }
+
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Changes the value of a tag, or adds it after the others
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn start(&self) -> &Position {
+        self.tree.start()
+    }
+
+    /// The moves of the main line
+    pub fn moves(&self) -> Vec<Move> {
+        self.tree.main_line()
+    }
+
+    /// The side lines, comments and NAGs along with the moves
+    pub fn tree(&self) -> &GameTree {
+        &self.tree
+    }
+
+    /// The game with every move of the main line played, ready to be
+    /// continued
+    pub fn to_game(&self) -> Game {
+        Game::from_tree(self.tree.clone())
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in self.tags.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn += &format!("[{} \"{}\"]\n", name, value);
+        }
+        pgn.push('\n');
+
+        let mut words = Vec::new();
+        if let Some(comment) = &self.tree.node(GameTree::ROOT).comment {
+            words.push(format!("{{{}}}", comment));
+        }
+        self.write_moves(GameTree::ROOT, *self.start(), true, &mut words);
+        words.push(self.tag("Result").unwrap_or("*").to_string());
+        pgn += &wrap(&words);
+        pgn
+    }
+
+    /// Writes the moves following the node, with each side line after the
+    /// move it replaces. A Black move gets its number when it starts a line or
+    /// comes after a comment or a side line.
+    fn write_moves(
+        &self,
+        mut node: NodeId,
+        mut position: Position,
+        mut show_number: bool,
+        words: &mut Vec<String>,
+    ) {
+        while let Some((&main, others)) = self.tree.node(node).children().split_first() {
+            self.write_move(main, &position, show_number, words);
+            for &other in others {
+                let first = words.len();
+                self.write_move(other, &position, true, words);
+                words[first].insert(0, '(');
+                let mut next = position;
+                next.make_move_unchecked(self.tree.node(other).mv().unwrap());
+                let show_number = self.tree.node(other).comment.is_some();
+                self.write_moves(other, next, show_number, words);
+                words.last_mut().unwrap().push(')');
+            }
+            show_number = !others.is_empty() || self.tree.node(main).comment.is_some();
+            position.make_move_unchecked(self.tree.node(main).mv().unwrap());
+            node = main;
+        }
+    }
+
+    fn write_move(
+        &self,
+        node: NodeId,
+        position: &Position,
+        show_number: bool,
+        words: &mut Vec<String>,
+    ) {
+        let node = self.tree.node(node);
+        let mv = node.mv().unwrap();
+        if let Some(comment) = &node.starting_comment {
+            words.push(format!("{{{}}}", comment));
+        }
+        let number = position.fullmove_number();
+        match position.side_to_move() {
+            PieceColor::White => words.push(format!("{}.", number)),
+            PieceColor::Black if show_number || node.starting_comment.is_some() => {
+                words.push(format!("{}...", number))
+            }
+            PieceColor::Black => {}
+        }
+        words.push(position.to_san(mv));
+        for nag in node.nags.iter() {
+            words.push(format!("${}", nag));
+        }
+        if let Some(comment) = &node.comment {
+            words.push(format!("{{{}}}", comment));
+        }
+    }
+}
+
+/// Joins the words with spaces into lines of at most 80 characters
+fn wrap(words: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for word in words {
+        if line_length > 0 && line_length + 1 + word.len() > 80 {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text += word;
+        line_length += word.len();
+    }
+    text.push('\n');
+    text
+}
+
+/// Reads every game in the text, with its variations, comments and NAGs
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    while tokens.peek().is_some() {
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(..)) = tokens.peek() {
+            if let Some(Token::Tag(name, value)) = tokens.next() {
+                tags.push((name, value));
+            }
+        }
+        // Chess960 games only need their castling rights read from X-FEN
+        let variant = match tags.iter().find(|(name, _)| name == "Variant") {
+            Some((_, name)) if name.eq_ignore_ascii_case("Chess960") => Variant::Standard,
+            Some((_, name)) => name
+                .parse()
+                .map_err(|_| PgnError::UnsupportedVariant(name.clone()))?,
+            None => Variant::Standard,
+        };
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => {
+                Position::from_variant_fen(fen, variant).map_err(PgnError::InvalidFen)?
+            }
+            None => variant.start_position(),
+        };
+        let mut game = PgnGame {
+            tags,
+            tree: GameTree::new(start),
+        };
+
+        let mut node = GameTree::ROOT;
+        let mut position = start;
+        // Where the lines the open variations branch off were left
+        let mut variations: Vec<(NodeId, Position)> = Vec::new();
+        // A comment at the start of a variation, before its first move
+        let mut starting_comment = None;
+        let mut at_variation_start = false;
+        while let Some(token) = tokens.peek() {
+            // The tags of the next game
+            if let Token::Tag(..) = token {
+                break;
+            }
+            match tokens.next().unwrap() {
+                Token::VariationStart => {
+                    let parent = game
+                        .tree
+                        .node(node)
+                        .parent()
+                        .ok_or(PgnError::MisplacedVariation)?;
+                    variations.push((node, position));
+                    node = parent;
+                    position = game.tree.position_at(parent);
+                    at_variation_start = true;
+                }
+                Token::VariationEnd => {
+                    let (last, last_position) =
+                        variations.pop().ok_or(PgnError::UnbalancedVariation)?;
+                    node = last;
+                    position = last_position;
+                    starting_comment = None;
+                    at_variation_start = false;
+                }
+                Token::San(san) => {
+                    let mv = position
+                        .parse_san(&san)
+                        .map_err(|error| PgnError::InvalidMove {
+                            number: position.fullmove_number(),
+                            color: position.side_to_move(),
+                            error,
+                        })?;
+                    position.make_move_unchecked(mv);
+                    node = game.tree.add_move(node, mv);
+                    if at_variation_start {
+                        game.tree.node_mut(node).starting_comment = starting_comment.take();
+                        at_variation_start = false;
+                    }
+                }
+                Token::Comment(comment) if at_variation_start => {
+                    append_comment(&mut starting_comment, comment)
+                }
+                Token::Comment(comment) => {
+                    append_comment(&mut game.tree.node_mut(node).comment, comment)
+                }
+                Token::Nag(nag) if !at_variation_start && node != GameTree::ROOT => {
+                    game.tree.node_mut(node).nags.push(nag)
+                }
+                Token::Result(result) if variations.is_empty() => {
+                    if game.tag("Result").is_none() {
+                        game.set_tag("Result", &result);
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if !variations.is_empty() {
+            return Err(PgnError::UnbalancedVariation);
+        }
+        games.push(game);
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::*;
+
+    const IMMORTAL_GAME: &str = r#"[Event "London"]
+[Site "London ENG"]
+[Date "1851.06.21"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Lionel Kieseritzky"]
+[Result "1-0"]
+
+1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 5. Bxb5 Nf6 6. Nf3 Qh6 7. d3 Nh5 8.
+Nh4 Qg5 9. Nf5 c6 10. g4 Nf6 11. Rg1 cxb5 12. h4 Qg6 13. h5 Qg5 14. Qf3 Ng8 15.
+Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2 18. Bd6 Bxg1 19. e5 Qxa1+ 20. Ke2 Na6 21.
+Nxg7+ Kd8 22. Qf6+ Nxf6 23. Be7# 1-0
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        let games = parse_pgn(IMMORTAL_GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.tag("White"), Some("Adolf Anderssen"));
+        assert_eq!(game.moves().len(), 45);
+        assert_eq!(game.to_pgn(), IMMORTAL_GAME);
+        let result = game.to_game().result().unwrap();
+        assert_eq!(result.reason, EndReason::Checkmate);
+        assert_eq!(result.score(), "1-0");
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#"[Event "Casual"]
+[SetUp "1"]
+[FEN "4k3/1P6/8/8/8/8/8/4K2R b K - 0 30"]
+
+30... Kd7 {The king runs} 31. b8=N+!? (31. O-O $2 Kc7) Kc7 32.O-O *
+
+[Event "Next"]
+
+1. e4 c5 1/2-1/2"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+        let game = &games[0];
+        assert_eq!(game.moves().len(), 4);
+        assert_eq!(
+            game.moves()[1].promotion(),
+            Some(PieceType::Knight),
+            "the variation after 31. b8=N+ is not part of the main line"
+        );
+        assert_eq!(game.tag("Result"), Some("*"));
+        assert!(game.to_pgn().ends_with(
+            "30... Kd7 {The king runs} 31. b8=N+ $5 (31. O-O $2 Kc7) 31... Kc7 32. O-O *\n"
+        ));
+        assert_eq!(games[1].tag("Result"), Some("1/2-1/2"));
+        assert_eq!(games[1].moves().len(), 2);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let text = r#"[Event "Training"]
+[Result "*"]
+
+{A quiet opening} 1. e4 $1 e5 (1... c5 {The Sicilian} 2. Nf3 (2. c3 d5) 2... d6)
+({Or} 1... e6 2. d4 (2. d3 $6) (2. Nc3) 2... d5) 2. Nf3 {Developing} 2... Nc6 $5
+3. Bb5 *
+"#;
+        let games = parse_pgn(text).unwrap();
+        let game = &games[0];
+        assert_eq!(game.to_pgn(), text);
+        assert_eq!(game.moves().len(), 5);
+
+        let tree = game.tree();
+        assert_eq!(
+            tree.node(GameTree::ROOT).comment.as_deref(),
+            Some("A quiet opening")
+        );
+        let e4 = tree.node(GameTree::ROOT).children()[0];
+        assert_eq!(tree.node(e4).nags, vec![1]);
+        let lines = tree.node(e4).children();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(tree.node(lines[1]).comment.as_deref(), Some("The Sicilian"));
+        assert_eq!(tree.node(lines[2]).starting_comment.as_deref(), Some("Or"));
+        let d4 = tree.node(lines[2]).children()[0];
+        assert_eq!(tree.node(d4).children().len(), 1);
+        assert_eq!(tree.node(tree.node(lines[2]).children()[1]).nags, vec![6]);
+
+        // Annotations stuck to moves are read as NAGs
+        let games = parse_pgn("1. e4!! e5?! 2. Qh5?? *").unwrap();
+        let tree = games[0].tree();
+        let mut node = GameTree::ROOT;
+        for nag in [3, 6, 4] {
+            node = tree.node(node).children()[0];
+            assert_eq!(tree.node(node).nags, vec![nag]);
+        }
+        assert!(games[0].to_pgn().ends_with("1. e4 $3 e5 $6 2. Qh5 $4 *\n"));
+
+        // Side lines stay in the game, and survive being saved again
+        let mut game = game.to_game();
+        while game.undo().is_some() {}
+        let e4 = game.lines()[0];
+        game.make_move(e4).unwrap();
+        assert_eq!(game.lines().len(), 3);
+        game.make_move(game.lines()[2]).unwrap();
+        let pgn = PgnGame::from_game(&game).to_pgn();
+        assert!(
+            pgn.contains("1. e4 $1 {Or} 1... e6 (1... e5 2. Nf3"),
+            "{}",
+            pgn
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_pgn("1. e4 e5 2. Ke3 *"),
+            Err(PgnError::InvalidMove {
+                number: 2,
+                color: PieceColor::White,
+                error: SanError::Illegal("Ke3".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_pgn("1. e4 (1. d4 *"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(
+            parse_pgn("(1. d4) 1. e4 *"),
+            Err(PgnError::MisplacedVariation)
+        );
+        assert_eq!(
+            parse_pgn("[Event \"Unfinished\""),
+            Err(PgnError::UnterminatedTag)
+        );
+        assert_eq!(
+            parse_pgn("1. e4 {no end"),
+            Err(PgnError::UnterminatedComment)
+        );
+        assert_eq!(
+            parse_pgn("[Variant \"Atomic\"]\n1. e4 *"),
+            Err(PgnError::UnsupportedVariant("Atomic".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_game() {
+        let mut game = Game::default();
+        let mv = game.position().parse_san("e4").unwrap();
+        game.make_move(mv).unwrap();
+        let pgn = PgnGame::from_game(&game).to_pgn();
+        assert!(pgn.starts_with("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n"));
+        assert!(pgn.ends_with("[Result \"*\"]\n\n1. e4 *\n"));
+
+        // The variant is kept, so that the moves are read under its rules
+        let mut game = Game::new(Position::default().with_variant(Variant::Antichess));
+        for san in ["e4", "d5", "exd5", "Qxd5"] {
+            let mv = game.position().parse_san(san).unwrap();
+            game.make_move(mv).unwrap();
+        }
+        let pgn = PgnGame::from_game(&game).to_pgn();
+        assert!(pgn.contains("[Variant \"Antichess\"]\n"), "{}", pgn);
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games[0].to_game().position(), game.position());
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::square::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceType {
+    King,
+    Queen,
+    Rook,
+    Bishop,
+    Knight,
+    Pawn,
+    /// A fairy piece moving like a bishop or a knight, as in Capablanca chess
+    Archbishop,
+    /// A fairy piece moving like a rook or a knight, as in Capablanca chess
+    Chancellor,
+}
+
+/// The ways pieces move, which each kind of piece combines
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Movement {
+    /// Any distance along ranks and files, up to the first piece in the way
+    Orthogonal,
+    /// Any distance along diagonals, up to the first piece in the way
+    Diagonal,
+    /// Two squares one way and one the other, jumping over any pieces
+    KnightLeap,
+    /// One square in any direction
+    KingStep,
+    /// One square diagonally forward, which is how pawns capture. Their
+    /// moves forward are not attacks, so the move generator adds them itself.
+    PawnCapture,
+}
+
+impl PieceType {
+    /// The letter for the piece in SAN and FEN, like `N` for a knight
+    pub fn letter(&self) -> char {
+        match self {
+            PieceType::King => 'K',
+            PieceType::Queen => 'Q',
+            PieceType::Rook => 'R',
+            PieceType::Bishop => 'B',
+            PieceType::Knight => 'N',
+            PieceType::Pawn => 'P',
+            PieceType::Archbishop => 'A',
+            PieceType::Chancellor => 'C',
+        }
+    }
+
+    /// The ways the piece moves, any one of which it may use on a move
+    pub const fn movements(&self) -> &'static [Movement] {
+        match self {
+            PieceType::King => &[Movement::KingStep],
+            PieceType::Queen => &[Movement::Orthogonal, Movement::Diagonal],
+            PieceType::Rook => &[Movement::Orthogonal],
+            PieceType::Bishop => &[Movement::Diagonal],
+            PieceType::Knight => &[Movement::KnightLeap],
+            PieceType::Pawn => &[Movement::PawnCapture],
+            PieceType::Archbishop => &[Movement::Diagonal, Movement::KnightLeap],
+            PieceType::Chancellor => &[Movement::Orthogonal, Movement::KnightLeap],
+        }
+    }
+
+    /// Reads a piece letter of either case
+    pub fn from_letter(c: char) -> Option<PieceType> {
+        PIECE_TYPES
+            .iter()
+            .copied()
+            .find(|kind| kind.letter() == c.to_ascii_uppercase())
+    }
+}
+
+pub const PIECE_TYPES: [PieceType; 8] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+    PieceType::Archbishop,
+    PieceType::Chancellor,
+];
+
+/// The pieces a pawn can promote to
+pub const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceColor {
+    White,
+    Black,
+}
+
+impl PieceColor {
+    pub fn opponent(&self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+
+    /// The rank direction this color's pawns advance in
+    pub fn forward(&self) -> i8 {
+        match self {
+            PieceColor::White => -1,
+            PieceColor::Black => 1,
+        }
+    }
+
+    /// The rank the king and rooks of this color start on
+    pub fn home_rank(&self) -> u8 {
+        match self {
+            PieceColor::White => 7,
+            PieceColor::Black => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Piece {
+    pub kind: PieceType,
+    pub color: PieceColor,
+    pub square: Square,
+}
+
+impl Piece {
+    /// The letter for the piece in FEN, upper case for White and lower case
+    /// for Black
+    pub fn letter(&self) -> char {
+        match self.color {
+            PieceColor::White => self.kind.letter(),
+            PieceColor::Black => self.kind.letter().to_ascii_lowercase(),
+        }
+    }
+}
+
+/// Text that is not a piece, a kind of piece or a color
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParsePieceError(pub String);
+
+impl fmt::Display for ParsePieceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}' is not a piece", self.0)
+    }
+}
+
+impl std::error::Error for ParsePieceError {}
+
+/// The upper case piece letter, like `N`
+impl fmt::Display for PieceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+impl FromStr for PieceType {
+    type Err = ParsePieceError;
+
+    fn from_str(text: &str) -> Result<PieceType, ParsePieceError> {
+        let mut chars = text.chars();
+        match (chars.next().and_then(PieceType::from_letter), chars.next()) {
+            (Some(kind), None) => Ok(kind),
+            _ => Err(ParsePieceError(text.to_string())),
+        }
+    }
+}
+
+/// `w` or `b`, as in FEN
+impl fmt::Display for PieceColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PieceColor::White => write!(f, "w"),
+            PieceColor::Black => write!(f, "b"),
+        }
+    }
+}
+
+impl FromStr for PieceColor {
+    type Err = ParsePieceError;
+
+    /// Reads `w` or `b`, or the color written out
+    fn from_str(text: &str) -> Result<PieceColor, ParsePieceError> {
+        match text.to_ascii_lowercase().as_str() {
+            "w" | "white" => Ok(PieceColor::White),
+            "b" | "black" => Ok(PieceColor::Black),
+            _ => Err(ParsePieceError(text.to_string())),
+        }
+    }
+}
+
+/// The FEN letter of the piece followed by its square, like `Ne4` for a white
+/// knight or `ne4` for a black one
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.letter(), self.square)
+    }
+}
+
+impl FromStr for Piece {
+    type Err = ParsePieceError;
+
+    fn from_str(text: &str) -> Result<Piece, ParsePieceError> {
+        let error = || ParsePieceError(text.to_string());
+        let c = text.chars().next().ok_or_else(error)?;
+        let kind = PieceType::from_letter(c).ok_or_else(error)?;
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        let square = text[c.len_utf8()..].parse().map_err(|_| error())?;
+        Ok(Piece {
+            kind,
+            color,
+            square,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::piece::*;
+
+    #[test]
+    fn test_notation() {
+        let knight = Piece {
+            kind: PieceType::Knight,
+            color: PieceColor::Black,
+            square: Square { x: 4, y: 4 },
+        };
+        assert_eq!(knight.to_string(), "ne4");
+        assert_eq!("ne4".parse(), Ok(knight));
+        assert_eq!(
+            "Ne4".parse::<Piece>().map(|piece| piece.color),
+            Ok(PieceColor::White)
+        );
+        for text in ["", "n", "xe4", "ne9"] {
+            assert_eq!(
+                text.parse::<Piece>(),
+                Err(ParsePieceError(text.to_string()))
+            );
+        }
+
+        for kind in PIECE_TYPES {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+            assert_eq!(kind.to_string().to_lowercase().parse(), Ok(kind));
+        }
+        assert!("Kn".parse::<PieceType>().is_err());
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+        assert_eq!("Black".parse(), Ok(PieceColor::Black));
+        assert!("red".parse::<PieceColor>().is_err());
+    }
+}
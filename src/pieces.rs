@@ -1,264 +1,216 @@
-use std::{collections::HashMap, f32::consts::FRAC_PI_2, vec};
+use std::{collections::HashMap, f32::consts::FRAC_PI_2};
 
 use bevy::prelude::*;
+use chess::game::*;
+use chess::piece::*;
+use chess::square::*;
 
 use crate::board::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub enum PieceType {
-    King,
-    Queen,
-    Rook,
-    Bishop,
-    Knight,
-    Pawn,
+struct PieceAssets {
+    kind_to_meshes: HashMap<PieceType, Vec<Handle<Mesh>>>,
+    color_to_material: HashMap<PieceColor, Handle<StandardMaterial>>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub enum PieceColor {
-    White,
-    Black,
-}
+impl FromWorld for PieceAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
 
-#[derive(Clone, Copy)]
-pub struct Piece {
-    pub kind: PieceType,
-    pub color: PieceColor,
-    pub square: Square,
-}
+        // Load all the meshes
+        let king_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh0/Primitive0");
+        let king_cross_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh1/Primitive0");
+        let pawn_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh2/Primitive0");
+        let knight_1_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh3/Primitive0");
+        let knight_2_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh4/Primitive0");
+        let rook_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh5/Primitive0");
+        let bishop_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh6/Primitive0");
+        let queen_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh7/Primitive0");
 
-impl Piece {
-    pub fn is_valid_move(&self, square: Square, pieces: Vec<Piece>) -> bool {
-        if get_piece_at(&pieces, square).map(|p| p.color) == Some(self.color) {
-            return false;
-        }
-        match self.kind {
-            PieceType::King => self.square.chebyshev_distance_to(square) == 1,
-            PieceType::Queen => {
-                (self.square.is_diagonal_to(square) || self.square.is_orthogonal_to(square))
-                    && is_path_free(self.square, square, &pieces)
-            }
-            PieceType::Rook => {
-                self.square.is_orthogonal_to(square) && is_path_free(self.square, square, &pieces)
-            }
-            PieceType::Bishop => {
-                self.square.is_diagonal_to(square) && is_path_free(self.square, square, &pieces)
-            }
-            PieceType::Knight => {
-                let rank_distance = self.square.rank_distance_to(square);
-                let file_distance = self.square.file_distance_to(square);
-                (rank_distance == 2 && file_distance == 1)
-                    || (rank_distance == 1 && file_distance == 2)
-            }
-            PieceType::Pawn => {
-                let opponent_color;
-                let two_square_advance_rank;
-                match self.color {
-                    PieceColor::White => {
-                        if square.y > self.square.y {
-                            return false;
-                        }
-                        opponent_color = PieceColor::Black;
-                        two_square_advance_rank = 6;
-                    }
-                    PieceColor::Black => {
-                        if square.y < self.square.y {
-                            return false;
-                        }
-                        opponent_color = PieceColor::White;
-                        two_square_advance_rank = 1;
-                    }
-                }
-                if self.square.is_on_same_file(square) {
-                    let rank_distance = self.square.rank_distance_to(square);
-                    if rank_distance == 1 {
-                        return get_piece_at(&pieces, square).is_none();
-                    }
-                    if rank_distance == 2 {
-                        return self.square.y == two_square_advance_rank
-                            && get_piece_at(&pieces, square).is_none()
-                            && is_path_free(self.square, square, &pieces);
-                    }
-                }
-                if self.square.is_diagonal_to(square)
-                    && self.square.chebyshev_distance_to(square) == 1
-                {
-                    return get_piece_at(&pieces, square).map(|p| p.color) == Some(opponent_color);
-                }
-                false
-            }
+        // Setup initial mesh translations
+        let mut kind_to_meshes = HashMap::new();
+        kind_to_meshes.insert(PieceType::King, vec![king_handle, king_cross_handle]);
+        kind_to_meshes.insert(PieceType::Queen, vec![queen_handle]);
+        kind_to_meshes.insert(PieceType::Rook, vec![rook_handle.clone()]);
+        kind_to_meshes.insert(PieceType::Bishop, vec![bishop_handle.clone()]);
+        kind_to_meshes.insert(
+            PieceType::Knight,
+            vec![knight_1_handle, knight_2_handle.clone()],
+        );
+        kind_to_meshes.insert(PieceType::Pawn, vec![pawn_handle]);
+        // Fairy pieces are drawn as the pieces whose moves they combine
+        kind_to_meshes.insert(
+            PieceType::Archbishop,
+            vec![bishop_handle, knight_2_handle.clone()],
+        );
+        kind_to_meshes.insert(PieceType::Chancellor, vec![rook_handle, knight_2_handle]);
+
+        // Add some materials
+        let mut color_to_material = HashMap::new();
+        color_to_material.insert(
+            PieceColor::White,
+            materials.add(Color::rgb(1., 0.8, 0.8).into()),
+        );
+        color_to_material.insert(
+            PieceColor::Black,
+            materials.add(Color::rgb(0., 0.2, 0.2).into()),
+        );
+
+        PieceAssets {
+            kind_to_meshes,
+            color_to_material,
         }
     }
 }
 
-fn get_piece_at(pieces: &Vec<Piece>, square: Square) -> Option<&Piece> {
-    pieces.iter().find(|piece| piece.square == square)
+fn spawn_piece_meshes(parent: &mut ChildBuilder, piece: Piece, assets: &PieceAssets) {
+    for mesh in assets.kind_to_meshes[&piece.kind].clone() {
+        parent.spawn_bundle(PbrBundle {
+            mesh,
+            material: assets.color_to_material[&piece.color].clone(),
+            ..Default::default()
+        });
+    }
 }
 
-fn is_path_free(begin: Square, end: Square, pieces: &Vec<Piece>) -> bool {
-    pieces
-        .iter()
-        .find(|piece| piece.square.is_inbetween(begin, end))
-        .is_none()
+/// Stands the piece on its square, facing the other side
+fn piece_transform(piece: Piece) -> Transform {
+    Transform {
+        translation: Vec3::new(piece.square.x as f32, 0., piece.square.y as f32),
+        rotation: if piece.color == PieceColor::White {
+            Quat::from_axis_angle(Vec3::new(0., 1., 0.), FRAC_PI_2)
+        } else {
+            Quat::from_axis_angle(Vec3::new(0., 1., 0.), -FRAC_PI_2)
+        },
+        scale: Vec3::new(0.2, 0.2, 0.2),
+    }
 }
 
-fn create_pieces(
+fn spawn_piece(commands: &mut Commands, piece: Piece, assets: &PieceAssets) {
+    commands
+        .spawn_bundle(PbrBundle {
+            transform: piece_transform(piece),
+            ..Default::default()
+        })
+        .insert(piece)
+        .with_children(|parent| spawn_piece_meshes(parent, piece, assets));
+}
+
+/// Brings the piece entities in line with the game whenever it changes. Pieces
+/// that moved slide over to their new square, captured pieces are despawned,
+/// promoted pawns swap their meshes and anything new is spawned.
+fn sync_pieces(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    game: Res<Game>,
+    assets: Res<PieceAssets>,
+    mut query: Query<(Entity, &mut Piece, &Children)>,
 ) {
-    // Load all the meshes
-    let king_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh0/Primitive0");
-    let king_cross_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh1/Primitive0");
-    let pawn_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh2/Primitive0");
-    let knight_1_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh3/Primitive0");
-    let knight_2_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh4/Primitive0");
-    let rook_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh5/Primitive0");
-    let bishop_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh6/Primitive0");
-    let queen_handle: Handle<Mesh> = asset_server.load("pieces.glb#Mesh7/Primitive0");
-
-    // Setup initial mesh translations
-    let mut kind_to_meshes = HashMap::new();
-    kind_to_meshes.insert(PieceType::King, vec![king_handle, king_cross_handle]);
-    kind_to_meshes.insert(PieceType::Queen, vec![queen_handle]);
-    kind_to_meshes.insert(PieceType::Rook, vec![rook_handle]);
-    kind_to_meshes.insert(PieceType::Bishop, vec![bishop_handle]);
-    kind_to_meshes.insert(PieceType::Knight, vec![knight_1_handle, knight_2_handle]);
-    kind_to_meshes.insert(PieceType::Pawn, vec![pawn_handle]);
-
-    // Add some materials
-    let mut color_to_material = HashMap::new();
-    color_to_material.insert(
-        PieceColor::White,
-        materials.add(Color::rgb(1., 0.8, 0.8).into()),
-    );
-    color_to_material.insert(
-        PieceColor::Black,
-        materials.add(Color::rgb(0., 0.2, 0.2).into()),
-    );
+    if !game.is_changed() {
+        return;
+    }
+    let pieces = game.position().pieces();
+    let entities: Vec<(Entity, Piece)> = query
+        .iter_mut()
+        .map(|(entity, piece, _)| (entity, *piece))
+        .collect();
+    let mut appeared: Vec<Piece> = pieces
+        .iter()
+        .filter(|piece| !entities.iter().any(|(_, other)| other == *piece))
+        .copied()
+        .collect();
+    let mut vanished: Vec<(Entity, Piece)> = entities
+        .into_iter()
+        .filter(|(_, piece)| !pieces.contains(piece))
+        .collect();
 
-    let mut pieces = vec![
-        // White back row
-        Piece {
-            kind: PieceType::Rook,
-            color: PieceColor::White,
-            square: Square { x: 0, y: 7 },
-        },
-        Piece {
-            kind: PieceType::Knight,
-            color: PieceColor::White,
-            square: Square { x: 1, y: 7 },
-        },
-        Piece {
-            kind: PieceType::Bishop,
-            color: PieceColor::White,
-            square: Square { x: 2, y: 7 },
-        },
-        Piece {
-            kind: PieceType::Queen,
-            color: PieceColor::White,
-            square: Square { x: 3, y: 7 },
-        },
-        Piece {
-            kind: PieceType::King,
-            color: PieceColor::White,
-            square: Square { x: 4, y: 7 },
-        },
-        Piece {
-            kind: PieceType::Bishop,
-            color: PieceColor::White,
-            square: Square { x: 5, y: 7 },
-        },
-        Piece {
-            kind: PieceType::Knight,
-            color: PieceColor::White,
-            square: Square { x: 6, y: 7 },
-        },
-        Piece {
-            kind: PieceType::Rook,
-            color: PieceColor::White,
-            square: Square { x: 7, y: 7 },
-        },
-        // Black back row
-        Piece {
-            kind: PieceType::Rook,
-            color: PieceColor::Black,
-            square: Square { x: 0, y: 0 },
-        },
-        Piece {
-            kind: PieceType::Knight,
-            color: PieceColor::Black,
-            square: Square { x: 1, y: 0 },
-        },
-        Piece {
-            kind: PieceType::Bishop,
-            color: PieceColor::Black,
-            square: Square { x: 2, y: 0 },
-        },
-        Piece {
-            kind: PieceType::Queen,
-            color: PieceColor::Black,
-            square: Square { x: 3, y: 0 },
-        },
-        Piece {
-            kind: PieceType::King,
-            color: PieceColor::Black,
-            square: Square { x: 4, y: 0 },
-        },
-        Piece {
-            kind: PieceType::Bishop,
-            color: PieceColor::Black,
-            square: Square { x: 5, y: 0 },
-        },
-        Piece {
-            kind: PieceType::Knight,
-            color: PieceColor::Black,
-            square: Square { x: 6, y: 0 },
-        },
-        Piece {
-            kind: PieceType::Rook,
-            color: PieceColor::Black,
-            square: Square { x: 7, y: 0 },
-        },
-    ];
-    // Add white and black pawns
-    for i in 0..8 {
-        pieces.push(Piece {
-            kind: PieceType::Pawn,
-            color: PieceColor::White,
-            square: Square { x: i, y: 6 },
-        });
-        pieces.push(Piece {
-            kind: PieceType::Pawn,
-            color: PieceColor::Black,
-            square: Square { x: i, y: 1 },
+    // Reuse the closest vanished entity for each piece that appeared, first
+    // looking for one of the same kind that moved, then for one of the same
+    // color that was promoted
+    for same_kind in [true, false] {
+        appeared.retain(|piece| {
+            let closest = vanished
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, old))| {
+                    old.color == piece.color && (!same_kind || old.kind == piece.kind)
+                })
+                .min_by_key(|(_, (_, old))| old.square.chebyshev_distance_to(piece.square))
+                .map(|(index, _)| index);
+            let (entity, old) = match closest {
+                Some(index) => vanished.remove(index),
+                None => return true,
+            };
+            if let Ok((_, mut component, children)) = query.get_mut(entity) {
+                *component = *piece;
+                if old.kind != piece.kind {
+                    for child in children.iter() {
+                        commands.entity(*child).despawn_recursive();
+                    }
+                    let piece = *piece;
+                    commands
+                        .entity(entity)
+                        .with_children(|parent| spawn_piece_meshes(parent, piece, &assets));
+                }
+            }
+            false
         });
     }
 
-    for piece in pieces {
-        commands
-            .spawn_bundle(PbrBundle {
-                transform: Transform {
-                    translation: Vec3::new(piece.square.x as f32, 0., piece.square.y as f32),
-                    rotation: if piece.color == PieceColor::White {
-                        Quat::from_axis_angle(Vec3::new(0., 1., 0.), FRAC_PI_2)
-                    } else {
-                        Quat::from_axis_angle(Vec3::new(0., 1., 0.), -FRAC_PI_2)
-                    },
-                    scale: Vec3::new(0.2, 0.2, 0.2),
-                },
-                ..Default::default()
-            })
-            .insert(piece)
-            .with_children(|parent| {
-                for mesh in kind_to_meshes[&piece.kind].clone() {
-                    parent.spawn_bundle(PbrBundle {
-                        mesh: mesh,
-                        material: color_to_material[&piece.color].clone(),
+    for (entity, _) in vanished {
+        commands.entity(entity).despawn_recursive();
+    }
+    for piece in appeared {
+        spawn_piece(&mut commands, piece, &assets);
+    }
+}
+
+// Component for a piece shown in a pocket beside the board in Crazyhouse
+struct PocketPiece;
+
+/// Shows the pieces in each pocket on its slots, fanned out outwards from the
+/// board when there is more than one of a kind
+fn sync_pocket_pieces(
+    mut commands: Commands,
+    game: Res<Game>,
+    assets: Res<PieceAssets>,
+    query: Query<Entity, With<PocketPiece>>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for color in [PieceColor::White, PieceColor::Black] {
+        let pocket = game.position().pocket(color);
+        let outwards = match color {
+            PieceColor::White => 0.2,
+            PieceColor::Black => -0.2,
+        };
+        for kind in pocket.kinds() {
+            for i in 0..pocket.count(kind) {
+                let piece = Piece {
+                    kind,
+                    color,
+                    square: Square { x: 0, y: 0 },
+                };
+                let translation = pocket_slot_translation(color, kind, game.position().files())
+                    + Vec3::new(outwards * i as f32, 0., 0.);
+                commands
+                    .spawn_bundle(PbrBundle {
+                        transform: Transform {
+                            translation,
+                            ..piece_transform(piece)
+                        },
                         ..Default::default()
-                    });
-                }
-            });
+                    })
+                    .insert(PocketPiece)
+                    .with_children(|parent| spawn_piece_meshes(parent, piece, &assets));
+            }
+        }
     }
 }
 
@@ -274,11 +226,45 @@ fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
     }
 }
 
+/// The move of a pawn to the last rank, waiting for the player to pick what it
+/// becomes. No other move can be made in the meantime.
+#[derive(Default)]
+pub struct PendingPromotion {
+    pub squares: Option<(Square, Square)>,
+}
+
+pub struct Promote {
+    pub kind: PieceType,
+}
+
+fn promote_pieces(
+    mut events: EventReader<Promote>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut move_requests: EventWriter<MoveRequest>,
+) {
+    for event in events.iter() {
+        if let Some((from, to)) = pending_promotion.squares.take() {
+            move_requests.send(MoveRequest::Move {
+                from,
+                to,
+                promotion: Some(event.kind),
+            });
+        }
+    }
+}
+
 pub struct PiecesPlugin;
 
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(create_pieces.system())
-            .add_system(move_pieces.system());
+        app.init_resource::<PieceAssets>()
+            .init_resource::<PendingPromotion>()
+            .add_event::<Promote>()
+            .add_system(sync_pieces.system())
+            .add_system(sync_pocket_pieces.system())
+            .add_system(move_pieces.system())
+            // Runs after the board has handled this frame's click, so the click
+            // that picked the promotion is not also taken as a move
+            .add_system_to_stage(CoreStage::PostUpdate, promote_pieces.system());
     }
 }
@@ -0,0 +1,1776 @@
+use std::fmt;
+
+use crate::attacks::*;
+use crate::bitboard::*;
+use crate::crazyhouse::*;
+use crate::fen::*;
+use crate::moves::*;
+use crate::piece::*;
+use crate::square::*;
+use crate::variant::*;
+use crate::zobrist::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CastlingSide {
+    Kingside,
+    Queenside,
+}
+
+/// Which castling moves each side may still make, as the file of the rook
+/// the king castles with. A right is lost for good once the king or that rook
+/// leaves its starting square. The rooks start on the a- and h-files in
+/// classical chess, and anywhere either side of the king in Chess960.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CastlingRights {
+    pub white_kingside: Option<u8>,
+    pub white_queenside: Option<u8>,
+    pub black_kingside: Option<u8>,
+    pub black_queenside: Option<u8>,
+}
+
+impl Default for CastlingRights {
+    fn default() -> Self {
+        CastlingRights {
+            white_kingside: Some(7),
+            white_queenside: Some(0),
+            black_kingside: Some(7),
+            black_queenside: Some(0),
+        }
+    }
+}
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights {
+        white_kingside: None,
+        white_queenside: None,
+        black_kingside: None,
+        black_queenside: None,
+    };
+
+    /// The file of the rook to castle with on that side, if the right is held
+    pub fn rook_file(&self, color: PieceColor, side: CastlingSide) -> Option<u8> {
+        match (color, side) {
+            (PieceColor::White, CastlingSide::Kingside) => self.white_kingside,
+            (PieceColor::White, CastlingSide::Queenside) => self.white_queenside,
+            (PieceColor::Black, CastlingSide::Kingside) => self.black_kingside,
+            (PieceColor::Black, CastlingSide::Queenside) => self.black_queenside,
+        }
+    }
+
+    pub fn rook_file_mut(&mut self, color: PieceColor, side: CastlingSide) -> &mut Option<u8> {
+        match (color, side) {
+            (PieceColor::White, CastlingSide::Kingside) => &mut self.white_kingside,
+            (PieceColor::White, CastlingSide::Queenside) => &mut self.white_queenside,
+            (PieceColor::Black, CastlingSide::Kingside) => &mut self.black_kingside,
+            (PieceColor::Black, CastlingSide::Queenside) => &mut self.black_queenside,
+        }
+    }
+
+    pub fn can_castle(&self, color: PieceColor, side: CastlingSide) -> bool {
+        self.rook_file(color, side).is_some()
+    }
+
+    /// Removes both rights of a side, for when its king moves
+    pub fn remove(&mut self, color: PieceColor) {
+        for side in CASTLING_SIDES {
+            *self.rook_file_mut(color, side) = None;
+        }
+    }
+
+    /// Removes the rights affected by a move from `from` to `to` of a rook
+    /// from its starting square, or capturing one there
+    pub fn update(&mut self, from: Square, to: Square) {
+        for color in [PieceColor::White, PieceColor::Black] {
+            for side in CASTLING_SIDES {
+                let right = self.rook_file_mut(color, side);
+                if let Some(x) = *right {
+                    let rook = Square {
+                        x,
+                        y: color.home_rank(),
+                    };
+                    if from == rook || to == rook {
+                        *right = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub const CASTLING_SIDES: [CastlingSide; 2] = [CastlingSide::Kingside, CastlingSide::Queenside];
+
+/// Where the king and rook end up after castling on the rank of a board with
+/// that many files: on the g- and f-files on the kingside, and the c- and
+/// d-files on the queenside, wherever they started. The kingside squares stay
+/// next to the last file on wider boards, like the i- and h-files on ten.
+pub fn castling_squares(side: CastlingSide, rank: u8, files: u8) -> (Square, Square) {
+    let (king, rook) = match side {
+        CastlingSide::Kingside => (files - 2, files - 3),
+        CastlingSide::Queenside => (2, 3),
+    };
+    (Square { x: king, y: rank }, Square { x: rook, y: rank })
+}
+
+/// The squares of the rank from one file to another, both included
+fn rank_span(rank: u8, a: u8, b: u8) -> Bitboard {
+    let mut span = Bitboard::EMPTY;
+    for x in a.min(b)..=a.max(b) {
+        span |= Bitboard::from_square(Square { x, y: rank });
+    }
+    span
+}
+
+/// Why a move cannot be made in the position
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IllegalMove {
+    /// There is no piece on the starting square
+    NoPiece,
+    /// The piece belongs to the side not to move
+    NotYourTurn,
+    /// The piece does not move that way, even on an empty board
+    CannotMoveThere,
+    /// Another piece stands in the way
+    BlockedPath,
+    OwnPieceOnTarget,
+    PawnBackwards,
+    /// A pawn moving diagonally without capturing
+    NothingToCapture,
+    /// The move would leave or put the mover's king in check
+    KingInCheck,
+    /// The king or the rook has moved
+    CastlingRightLost,
+    CastlingThroughCheck,
+    /// A pawn reaching the last rank must be given a piece to promote to
+    PromotionNeeded,
+    /// A piece to promote to given for a move that cannot promote to it
+    InvalidPromotion,
+    /// Another piece can capture, in a variant where capturing is compulsory
+    CaptureRequired,
+    /// A drop of a piece the side does not have in its pocket
+    NotInPocket,
+    /// A drop onto a square that is not empty
+    OccupiedSquare,
+    PawnDropOnBackRank,
+    /// A square past the last file of a narrower board
+    OffBoard,
+    GameOver,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            IllegalMove::NoPiece => "there is no piece there to move",
+            IllegalMove::NotYourTurn => "it is the other side's turn",
+            IllegalMove::CannotMoveThere => "the piece does not move that way",
+            IllegalMove::BlockedPath => "the path is blocked",
+            IllegalMove::OwnPieceOnTarget => "a piece cannot capture its own side",
+            IllegalMove::PawnBackwards => "pawns cannot move backwards",
+            IllegalMove::NothingToCapture => "pawns only move diagonally to capture",
+            IllegalMove::KingInCheck => "the king would be in check",
+            IllegalMove::CastlingRightLost => "the king or rook has already moved",
+            IllegalMove::CastlingThroughCheck => "the king cannot castle out of or through check",
+            IllegalMove::PromotionNeeded => "the pawn must promote",
+            IllegalMove::InvalidPromotion => "the move cannot promote to that piece",
+            IllegalMove::CaptureRequired => "a capture must be made when there is one",
+            IllegalMove::NotInPocket => "there is no such piece in the pocket",
+            IllegalMove::OccupiedSquare => "pieces can only be dropped on empty squares",
+            IllegalMove::PawnDropOnBackRank => "pawns cannot be dropped on the first or last rank",
+            IllegalMove::OffBoard => "the square is not on the board",
+            IllegalMove::GameOver => "the game is over",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+impl IllegalMove {
+    /// Whether the reason can give away where the other side's pieces are,
+    /// which in dark chess the player may not be able to see
+    pub fn depends_on_opponent(&self) -> bool {
+        matches!(
+            self,
+            IllegalMove::BlockedPath
+                | IllegalMove::NothingToCapture
+                | IllegalMove::KingInCheck
+                | IllegalMove::CastlingThroughCheck
+                | IllegalMove::CaptureRequired
+                | IllegalMove::OccupiedSquare
+        )
+    }
+}
+
+/// A move that was made, with what `unmake_move` needs to take it back: the
+/// state the move destroyed, which cannot be worked out from the move alone
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Undo {
+    pub mv: Move,
+    captured: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant_square: Option<Square>,
+    halfmove_clock: u32,
+    checks: [u8; 2],
+    pockets: [Pocket; 2],
+    promoted: Bitboard,
+    hash: u64,
+}
+
+/// The state of the board between two moves: where the pieces are, whose turn
+/// it is, and what the rules need to remember about earlier moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Position {
+    /// The squares of each kind of piece, indexed by `PieceType as usize`
+    kinds: [Bitboard; PIECE_TYPES.len()],
+    /// The squares of each side's pieces, indexed by `PieceColor as usize`
+    colors: [Bitboard; 2],
+    side_to_move: PieceColor,
+    castling_rights: CastlingRights,
+    /// The square a pawn skipped over with a two-square advance on the last
+    /// move. It can only be captured en passant on the very next move.
+    en_passant_square: Option<Square>,
+    /// The number of moves since the last capture or pawn move
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    /// The width of the board, which is eight files apart from in variants
+    /// like Capablanca chess
+    files: u8,
+    /// The rules the game is played under
+    variant: Variant,
+    /// The number of checks each side has given, which Three-check counts
+    checks: [u8; 2],
+    /// The pieces each side can drop in Crazyhouse, indexed by
+    /// `PieceColor as usize`
+    pockets: [Pocket; 2],
+    /// The squares of the pieces that were pawns before promoting, which
+    /// Crazyhouse puts back in the pocket as pawns
+    promoted: Bitboard,
+    /// Kept up to date by every change to the fields above it
+    hash: u64,
+}
+
+impl Default for Position {
+    /// The standard starting position
+    fn default() -> Self {
+        Position::from_fen(START_FEN).unwrap()
+    }
+}
+
+impl Position {
+    pub fn new(
+        pieces: Vec<Piece>,
+        side_to_move: PieceColor,
+        castling_rights: CastlingRights,
+        en_passant_square: Option<Square>,
+    ) -> Position {
+        let mut position = Position {
+            kinds: [Bitboard::EMPTY; PIECE_TYPES.len()],
+            colors: [Bitboard::EMPTY; 2],
+            side_to_move,
+            castling_rights,
+            en_passant_square,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            files: 8,
+            variant: Variant::Standard,
+            checks: [0; 2],
+            pockets: [Pocket::EMPTY; 2],
+            promoted: Bitboard::EMPTY,
+            hash: castling_key(castling_rights),
+        };
+        for piece in pieces {
+            position.put(piece);
+        }
+        if side_to_move == PieceColor::Black {
+            position.hash ^= black_to_move_key();
+        }
+        position.hash ^= position.en_passant_hash();
+        position
+    }
+
+    /// Sets how many moves were made since the last capture or pawn move, and
+    /// the number of the next full move
+    pub fn with_clocks(self, halfmove_clock: u32, fullmove_number: u32) -> Position {
+        Position {
+            halfmove_clock,
+            fullmove_number,
+            ..self
+        }
+    }
+
+    /// The same pieces on a board with that many files, from 1 to
+    /// `MAX_FILES`. No piece may stand past the last file.
+    pub fn with_files(self, files: u8) -> Position {
+        Position { files, ..self }
+    }
+
+    /// The number of files of the board
+    pub fn files(&self) -> u8 {
+        self.files
+    }
+
+    /// Every square of the board
+    pub fn board(&self) -> Bitboard {
+        Bitboard::board(self.files)
+    }
+
+    /// The same position played under the rules of a variant. Castling rights
+    /// are dropped where the king cannot castle.
+    pub fn with_variant(mut self, variant: Variant) -> Position {
+        self.variant = variant;
+        if !variant.has_royal_king() {
+            self.hash ^= castling_key(self.castling_rights);
+            self.castling_rights = CastlingRights::NONE;
+            self.hash ^= castling_key(self.castling_rights);
+        }
+        self
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Fills the pockets, indexed by `PieceColor as usize`, and marks the
+    /// promoted pieces for Crazyhouse
+    pub fn with_pockets(mut self, pockets: [Pocket; 2], promoted: Bitboard) -> Position {
+        for color in [PieceColor::White, PieceColor::Black] {
+            for kind in pockets[color as usize].kinds() {
+                for _ in 0..pockets[color as usize].count(kind) {
+                    self.add_to_pocket(color, kind);
+                }
+            }
+        }
+        self.promoted = promoted;
+        self
+    }
+
+    pub fn pocket(&self, color: PieceColor) -> Pocket {
+        self.pockets[color as usize]
+    }
+
+    /// The squares of the pieces that were promoted, as far as Crazyhouse
+    /// keeps track of them
+    pub fn promoted(&self) -> Bitboard {
+        self.promoted
+    }
+
+    fn add_to_pocket(&mut self, color: PieceColor, kind: PieceType) {
+        let pocket = &mut self.pockets[color as usize];
+        pocket.add(kind);
+        self.hash ^= pocket_key(color, kind, pocket.count(kind));
+    }
+
+    fn take_from_pocket(&mut self, color: PieceColor, kind: PieceType) {
+        let pocket = &mut self.pockets[color as usize];
+        self.hash ^= pocket_key(color, kind, pocket.count(kind));
+        pocket.remove(kind);
+    }
+
+    /// The number of checks the side has given in Three-check
+    pub fn checks_given(&self, color: PieceColor) -> u8 {
+        self.checks[color as usize]
+    }
+
+    pub fn pieces(&self) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+        for color in [PieceColor::White, PieceColor::Black] {
+            for kind in PIECE_TYPES {
+                for square in self.pieces_of(kind, color).squares() {
+                    pieces.push(Piece {
+                        kind,
+                        color,
+                        square,
+                    });
+                }
+            }
+        }
+        pieces
+    }
+
+    /// The squares of the pieces of one kind and color
+    pub fn pieces_of(&self, kind: PieceType, color: PieceColor) -> Bitboard {
+        self.kinds[kind as usize] & self.colors[color as usize]
+    }
+
+    /// The squares of all the pieces of one color
+    pub fn pieces_of_color(&self, color: PieceColor) -> Bitboard {
+        self.colors[color as usize]
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let color = if self.colors[PieceColor::White as usize].contains(square) {
+            PieceColor::White
+        } else if self.colors[PieceColor::Black as usize].contains(square) {
+            PieceColor::Black
+        } else {
+            return None;
+        };
+        let kind = *PIECE_TYPES
+            .iter()
+            .find(|kind| self.kinds[**kind as usize].contains(square))?;
+        Some(Piece {
+            kind,
+            color,
+            square,
+        })
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.side_to_move
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling_rights
+    }
+
+    pub fn en_passant_square(&self) -> Option<Square> {
+        self.en_passant_square
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    fn put(&mut self, piece: Piece) {
+        let bit = Bitboard::from_square(piece.square);
+        self.kinds[piece.kind as usize] |= bit;
+        self.colors[piece.color as usize] |= bit;
+        self.hash ^= piece_key(piece);
+    }
+
+    fn remove(&mut self, piece: Piece) {
+        let bit = !Bitboard::from_square(piece.square);
+        self.kinds[piece.kind as usize] &= bit;
+        self.colors[piece.color as usize] &= bit;
+        self.hash ^= piece_key(piece);
+    }
+
+    /// The pieces of the color that attack the square
+    pub fn attackers(&self, square: Square, by_color: PieceColor) -> Bitboard {
+        self.attackers_among(
+            square,
+            by_color,
+            self.occupied(),
+            self.colors[by_color as usize],
+        )
+    }
+
+    /// The pieces of the color on the `pieces` squares that would attack the
+    /// square with those squares occupied, for looking at the position a move
+    /// leads to without making it
+    fn attackers_among(
+        &self,
+        square: Square,
+        by_color: PieceColor,
+        occupied: Bitboard,
+        pieces: Bitboard,
+    ) -> Bitboard {
+        let by = |kind| self.kinds[kind as usize] & pieces;
+        // A piece attacks the square if a piece moving the same way from the
+        // square would attack it back, which for pawns means one of the other
+        // color. The kinds moving each way are the ones whose `movements`
+        // include it, written out as this is the hottest part of move
+        // generation.
+        let leapers = by(PieceType::Knight) | by(PieceType::Archbishop) | by(PieceType::Chancellor);
+        let orthogonal = by(PieceType::Rook) | by(PieceType::Queen) | by(PieceType::Chancellor);
+        let diagonal = by(PieceType::Bishop) | by(PieceType::Queen) | by(PieceType::Archbishop);
+        (pawn_attacks(by_color.opponent(), square) & by(PieceType::Pawn))
+            | (knight_attacks(square) & leapers)
+            | (king_attacks(square) & by(PieceType::King))
+            | (rook_attacks(square, occupied) & orthogonal)
+            | (bishop_attacks(square, occupied) & diagonal)
+    }
+
+    pub fn is_square_attacked(&self, square: Square, by_color: PieceColor) -> bool {
+        !self.attackers(square, by_color).is_empty()
+    }
+
+    fn is_king_attacked(&self, color: PieceColor) -> bool {
+        match self.pieces_of(PieceType::King, color).first() {
+            Some(king) => self.is_square_attacked(king, color.opponent()),
+            None => false,
+        }
+    }
+
+    /// Adds every move of the piece on the square that follows the way it
+    /// moves, whether or not it leaves its own king in check
+    fn pseudo_legal_moves_from(&self, from: Square, moves: &mut Vec<Move>) {
+        let piece = match self.piece_at(from) {
+            Some(piece) if piece.color == self.side_to_move => piece,
+            _ => return,
+        };
+        let own = self.colors[piece.color as usize];
+        let targets = match piece.kind {
+            PieceType::Pawn => return self.pawn_moves(piece, moves),
+            PieceType::King => {
+                self.castling_moves(piece, moves);
+                king_attacks(from)
+            }
+            _ => piece_attacks(piece, self.occupied()),
+        };
+        for to in (targets & self.board() & !own).squares() {
+            moves.push(Move {
+                from,
+                to,
+                kind: MoveKind::Normal,
+            });
+        }
+    }
+
+    fn pawn_moves(&self, pawn: Piece, moves: &mut Vec<Move>) {
+        let from = pawn.square;
+        let forward = pawn.color.forward();
+        let mut push = |to: Square, kind| {
+            if to.y == pawn.color.opponent().home_rank() {
+                for kind in self.variant.promotion_pieces().iter().copied() {
+                    moves.push(Move {
+                        from,
+                        to,
+                        kind: MoveKind::Promotion(kind),
+                    });
+                }
+            } else {
+                moves.push(Move { from, to, kind });
+            }
+        };
+
+        let occupied = self.occupied();
+        let single_y = from.y as i8 + forward;
+        if (0..RANKS as i8).contains(&single_y) {
+            let single = Square {
+                x: from.x,
+                y: single_y as u8,
+            };
+            if !occupied.contains(single) {
+                push(single, MoveKind::Normal);
+                let double = Square {
+                    x: from.x,
+                    y: (single_y + forward) as u8,
+                };
+                // Pawns start one rank in front of the king
+                if from.y as i8 == pawn.color.home_rank() as i8 + forward
+                    && !occupied.contains(double)
+                {
+                    push(double, MoveKind::DoublePawnPush);
+                }
+            }
+        }
+
+        let attacks = pawn_attacks(pawn.color, from);
+        let enemies = self.colors[pawn.color.opponent() as usize];
+        for to in (attacks & enemies).squares() {
+            push(to, MoveKind::Normal);
+        }
+        if let Some(square) = self.en_passant_square {
+            if attacks.contains(square) {
+                push(square, MoveKind::EnPassant);
+            }
+        }
+    }
+
+    fn castling_moves(&self, king: Piece, moves: &mut Vec<Move>) {
+        for side in CASTLING_SIDES {
+            if let (Some(rook), None) = (
+                self.castling_rook(king, side),
+                self.castling_problem(king, side),
+            ) {
+                moves.push(Move {
+                    from: king.square,
+                    to: rook,
+                    kind: MoveKind::Castling(side),
+                });
+            }
+        }
+    }
+
+    /// The square of the rook the king castles with on that side, if the
+    /// right is held and both are still on the back rank
+    fn castling_rook(&self, king: Piece, side: CastlingSide) -> Option<Square> {
+        let rank = king.color.home_rank();
+        let x = self.castling_rights.rook_file(king.color, side)?;
+        let rook = Square { x, y: rank };
+        let on_side = match side {
+            CastlingSide::Kingside => x > king.square.x,
+            CastlingSide::Queenside => x < king.square.x,
+        };
+        if king.square.y == rank
+            && on_side
+            && self.pieces_of(PieceType::Rook, king.color).contains(rook)
+        {
+            Some(rook)
+        } else {
+            None
+        }
+    }
+
+    /// What stops the king castling on that side, if anything. Every square
+    /// the king or rook crosses or lands on must be empty apart from the two of
+    /// them, and the king may not castle out of or through check; landing in
+    /// check is ruled out like for any other move.
+    fn castling_problem(&self, king: Piece, side: CastlingSide) -> Option<IllegalMove> {
+        let rook = match self.castling_rook(king, side) {
+            Some(rook) => rook,
+            None => return Some(IllegalMove::CastlingRightLost),
+        };
+        let rank = rook.y;
+        let (king_to, rook_to) = castling_squares(side, rank, self.files);
+        let king_path = rank_span(rank, king.square.x, king_to.x);
+        let others =
+            self.occupied() & !Bitboard::from_square(king.square) & !Bitboard::from_square(rook);
+        if !((king_path | rank_span(rank, rook.x, rook_to.x)) & others).is_empty() {
+            return Some(IllegalMove::BlockedPath);
+        }
+        let crossed =
+            (king_path & !Bitboard::from_square(king_to)) | Bitboard::from_square(king.square);
+        let opponent = king.color.opponent();
+        if crossed
+            .squares()
+            .any(|square| self.is_square_attacked(square, opponent))
+        {
+            return Some(IllegalMove::CastlingThroughCheck);
+        }
+        None
+    }
+
+    /// Adds every drop from the pocket of the side to move, in a variant that
+    /// has them
+    fn drop_moves(&self, moves: &mut Vec<Move>) {
+        if !self.variant.has_drops() {
+            return;
+        }
+        let empty = self.board() & !self.occupied();
+        for kind in self.pockets[self.side_to_move as usize].kinds() {
+            for to in empty.squares() {
+                if kind != PieceType::Pawn || (to.y != 0 && to.y != RANKS - 1) {
+                    moves.push(Move {
+                        from: to,
+                        to,
+                        kind: MoveKind::Drop(kind),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Looks up the legal drop of the piece from the pocket of the side to
+    /// move onto the square, or tells why there is none
+    pub fn check_drop(&self, kind: PieceType, to: Square) -> Result<Move, IllegalMove> {
+        if !self.variant.has_drops() || self.pockets[self.side_to_move as usize].count(kind) == 0 {
+            return Err(IllegalMove::NotInPocket);
+        }
+        if !self.board().contains(to) {
+            return Err(IllegalMove::OffBoard);
+        }
+        if self.occupied().contains(to) {
+            return Err(IllegalMove::OccupiedSquare);
+        }
+        if kind == PieceType::Pawn && (to.y == 0 || to.y == RANKS - 1) {
+            return Err(IllegalMove::PawnDropOnBackRank);
+        }
+        let mv = Move {
+            from: to,
+            to,
+            kind: MoveKind::Drop(kind),
+        };
+        if !self.is_safe(mv) {
+            return Err(IllegalMove::KingInCheck);
+        }
+        Ok(mv)
+    }
+
+    /// The squares the side sees in dark chess: those of its own pieces and
+    /// every square they could move to, were it their turn, including the
+    /// pawn an en passant capture would take
+    pub fn visible_squares(&self, color: PieceColor) -> Bitboard {
+        let mut position = *self;
+        position.side_to_move = color;
+        if color != self.side_to_move {
+            position.en_passant_square = None;
+        }
+        let own = self.pieces_of_color(color);
+        let mut moves = Vec::new();
+        for from in own.squares() {
+            position.pseudo_legal_moves_from(from, &mut moves);
+        }
+        let mut visible = own;
+        for mv in moves {
+            visible |= Bitboard::from_square(mv.to);
+            if mv.kind == MoveKind::EnPassant {
+                visible |= Bitboard::from_square(Square {
+                    x: mv.to.x,
+                    y: mv.from.y,
+                });
+            }
+        }
+        visible
+    }
+
+    /// Whether making the pseudo-legal move keeps the mover's king safe
+    fn is_safe(&self, mv: Move) -> bool {
+        if !self.variant.has_royal_king() {
+            return true;
+        }
+        let color = self.side_to_move;
+        let (from, to) = (mv.from, mv.to);
+        let mut king = self.pieces_of(PieceType::King, color).first();
+        let mut occupied = self.occupied();
+        let mut opponents = self.colors[color.opponent() as usize];
+        match mv.kind {
+            MoveKind::Drop(_) => occupied |= Bitboard::from_square(to),
+            MoveKind::Castling(side) => {
+                let (king_to, rook_to) = castling_squares(side, from.y, self.files);
+                occupied &= !Bitboard::from_square(from) & !Bitboard::from_square(to);
+                occupied |= Bitboard::from_square(king_to) | Bitboard::from_square(rook_to);
+                king = Some(king_to);
+            }
+            _ => {
+                // The pawn captured en passant stands beside the moving one
+                let captured = match mv.kind {
+                    MoveKind::EnPassant => Square { x: to.x, y: from.y },
+                    _ => to,
+                };
+                occupied &= !Bitboard::from_square(from) & !Bitboard::from_square(captured);
+                occupied |= Bitboard::from_square(to);
+                opponents &= !Bitboard::from_square(captured);
+                if king == Some(from) {
+                    king = Some(to);
+                }
+            }
+        }
+        // Only the squares the pieces stand on change, so rather than make the
+        // move, look for attacks on the king with them changed
+        match king {
+            Some(king) => self
+                .attackers_among(king, color.opponent(), occupied, opponents)
+                .is_empty(),
+            None => true,
+        }
+    }
+
+    /// Looks up the legal move of the piece on `from` to `to`. Promotions need
+    /// the piece to promote to, and no other move may have one.
+    pub fn find_move(
+        &self,
+        from: Square,
+        to: Square,
+        promotion: Option<PieceType>,
+    ) -> Option<Move> {
+        self.check_move(from, to, promotion).ok()
+    }
+
+    /// Looks up a legal move like `find_move`, or tells why there is none
+    pub fn check_move(
+        &self,
+        from: Square,
+        to: Square,
+        promotion: Option<PieceType>,
+    ) -> Result<Move, IllegalMove> {
+        let piece = self.piece_at(from).ok_or(IllegalMove::NoPiece)?;
+        if piece.color != self.side_to_move {
+            return Err(IllegalMove::NotYourTurn);
+        }
+        if !self.board().contains(to) {
+            return Err(IllegalMove::OffBoard);
+        }
+        let mut moves = Vec::new();
+        self.pseudo_legal_moves_from(from, &mut moves);
+        if moves.iter().any(|mv| mv.to == to) {
+            moves.retain(|mv| mv.to == to);
+        } else {
+            // Castling may also be given as the square the king lands on
+            moves.retain(|mv| {
+                matches!(mv.kind, MoveKind::Castling(side) if castling_squares(side, from.y, self.files).0 == to)
+            });
+        }
+        if moves.is_empty() {
+            return Err(self.unreachable_reason(piece, to));
+        }
+        moves.retain(|mv| self.is_safe(*mv));
+        if moves.is_empty() {
+            return Err(IllegalMove::KingInCheck);
+        }
+        if !self.is_capture(moves[0]) && self.is_capture_required() {
+            return Err(IllegalMove::CaptureRequired);
+        }
+        moves
+            .into_iter()
+            .find(|mv| mv.promotion() == promotion)
+            .ok_or(match promotion {
+                Some(_) => IllegalMove::InvalidPromotion,
+                None => IllegalMove::PromotionNeeded,
+            })
+    }
+
+    /// Why the piece of the side to move has no move at all to the square
+    fn unreachable_reason(&self, piece: Piece, to: Square) -> IllegalMove {
+        let from = piece.square;
+        // Castling is asked for by moving the king onto its rook, or along the
+        // back rank to where it lands
+        let rank = piece.color.home_rank();
+        let lands_on = |side| castling_squares(side, rank, self.files).0 == to;
+        if piece.kind == PieceType::King
+            && from.y == rank
+            && to.y == rank
+            && (self.pieces_of(PieceType::Rook, piece.color).contains(to)
+                || (from.file_distance_to(to) >= 2 && CASTLING_SIDES.iter().any(|s| lands_on(*s))))
+        {
+            let side = if to.x > from.x {
+                CastlingSide::Kingside
+            } else {
+                CastlingSide::Queenside
+            };
+            return self
+                .castling_problem(piece, side)
+                .unwrap_or(IllegalMove::CannotMoveThere);
+        }
+        if self.colors[piece.color as usize].contains(to) {
+            return IllegalMove::OwnPieceOnTarget;
+        }
+        match piece.kind {
+            PieceType::Pawn => {
+                let forward = piece.color.forward();
+                let steps = (to.y as i8 - from.y as i8) * forward;
+                // Only a pawn on its starting square may advance two
+                let max_steps = if from.y as i8 == piece.color.home_rank() as i8 + forward {
+                    2
+                } else {
+                    1
+                };
+                if steps < 0 {
+                    IllegalMove::PawnBackwards
+                } else if pawn_attacks(piece.color, from).contains(to) {
+                    IllegalMove::NothingToCapture
+                } else if to.x != from.x || steps == 0 || steps > max_steps {
+                    IllegalMove::CannotMoveThere
+                } else {
+                    IllegalMove::BlockedPath
+                }
+            }
+            _ => {
+                if piece_attacks(piece, Bitboard::EMPTY).contains(to) {
+                    IllegalMove::BlockedPath
+                } else {
+                    IllegalMove::CannotMoveThere
+                }
+            }
+        }
+    }
+
+    pub fn is_legal(&self, mv: Move) -> bool {
+        match mv.kind {
+            MoveKind::Drop(kind) => self.check_drop(kind, mv.to) == Ok(mv),
+            _ => self.find_move(mv.from, mv.to, mv.promotion()) == Some(mv),
+        }
+    }
+
+    /// Whether the move takes a piece of the other side
+    pub fn is_capture(&self, mv: Move) -> bool {
+        match mv.kind {
+            MoveKind::EnPassant => true,
+            MoveKind::Castling(_) => false,
+            _ => self.colors[self.side_to_move.opponent() as usize].contains(mv.to),
+        }
+    }
+
+    /// Whether only captures may be played, as the variant makes them
+    /// compulsory and there is one
+    fn is_capture_required(&self) -> bool {
+        self.variant.captures_compulsory()
+            && self
+                .unrestricted_moves()
+                .into_iter()
+                .any(|mv| self.is_capture(mv))
+    }
+
+    /// Every move of the piece on the square that keeps the king safe,
+    /// whether or not another piece has to capture instead
+    fn unrestricted_moves_from(&self, from: Square) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.pseudo_legal_moves_from(from, &mut moves);
+        moves.retain(|mv| self.is_safe(*mv));
+        moves
+    }
+
+    fn unrestricted_moves(&self) -> Vec<Move> {
+        let mut moves: Vec<Move> = self
+            .pieces_of_color(self.side_to_move)
+            .squares()
+            .flat_map(|from| self.unrestricted_moves_from(from))
+            .collect();
+        let mut drops = Vec::new();
+        self.drop_moves(&mut drops);
+        moves.extend(drops.into_iter().filter(|mv| self.is_safe(*mv)));
+        moves
+    }
+
+    /// Every legal drop of a piece of the kind from the pocket of the side to
+    /// move
+    pub fn legal_drops(&self, kind: PieceType) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.drop_moves(&mut moves);
+        moves.retain(|mv| mv.kind == MoveKind::Drop(kind) && self.is_safe(*mv));
+        moves
+    }
+
+    /// Every legal move of the piece on the square, with a separate move for
+    /// each piece a pawn can promote to
+    pub fn legal_moves_from(&self, from: Square) -> Vec<Move> {
+        let mut moves = self.unrestricted_moves_from(from);
+        if self.is_capture_required() {
+            moves.retain(|mv| self.is_capture(*mv));
+        }
+        moves
+    }
+
+    /// Every legal move of the side to move
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = self.unrestricted_moves();
+        if self.variant.captures_compulsory() && moves.iter().any(|mv| self.is_capture(*mv)) {
+            moves.retain(|mv| self.is_capture(*mv));
+        }
+        moves
+    }
+
+    pub fn make_move(&mut self, mv: Move) -> Result<Undo, IllegalMove> {
+        if let MoveKind::Drop(kind) = mv.kind {
+            self.check_drop(kind, mv.to)?;
+            return Ok(self.make_move_unchecked(mv));
+        }
+        match self.check_move(mv.from, mv.to, mv.promotion())? {
+            legal if legal == mv => Ok(self.make_move_unchecked(mv)),
+            // Only the kind of the move is wrong
+            _ => Err(IllegalMove::CannotMoveThere),
+        }
+    }
+
+    /// Makes a move without checking that it is legal, for moves that just
+    /// came out of `legal_moves`
+    pub fn make_move_unchecked(&mut self, mv: Move) -> Undo {
+        let (from, to) = (mv.from, mv.to);
+        let mut undo = Undo {
+            mv,
+            captured: None,
+            castling_rights: self.castling_rights,
+            en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            checks: self.checks,
+            pockets: self.pockets,
+            promoted: self.promoted,
+            hash: self.hash,
+        };
+        let piece = match (mv.kind, self.piece_at(from)) {
+            (MoveKind::Drop(kind), _) => Piece {
+                kind,
+                color: self.side_to_move,
+                square: to,
+            },
+            (_, Some(piece)) => piece,
+            (_, None) => return undo,
+        };
+        self.hash ^= self.en_passant_hash() ^ castling_key(self.castling_rights);
+        // The pawn captured en passant stands beside the moving one
+        let captured = match mv.kind {
+            MoveKind::EnPassant => self.piece_at(Square { x: to.x, y: from.y }),
+            // Castling moves the king onto its own rook
+            MoveKind::Castling(_) => None,
+            _ => self.piece_at(to),
+        };
+        if let Some(captured) = captured {
+            self.remove(captured);
+            if self.variant.has_drops() {
+                let kind = if self.promoted.contains(captured.square) {
+                    PieceType::Pawn
+                } else {
+                    captured.kind
+                };
+                self.add_to_pocket(piece.color, kind);
+            }
+        }
+        undo.captured = captured;
+        if self.variant.has_drops() {
+            let was_promoted = self.promoted.contains(from);
+            for square in [from, to].iter().chain(captured.map(|c| c.square).iter()) {
+                self.promoted &= !Bitboard::from_square(*square);
+            }
+            if was_promoted || mv.promotion().is_some() {
+                self.promoted |= Bitboard::from_square(to);
+            }
+        }
+        if let MoveKind::Castling(side) = mv.kind {
+            // Both pieces come off before either goes back, as in Chess960 the
+            // king may land where the rook stood or the other way around
+            let (king_to, rook_to) = castling_squares(side, from.y, self.files);
+            let rook = Piece {
+                kind: PieceType::Rook,
+                color: piece.color,
+                square: to,
+            };
+            self.remove(piece);
+            self.remove(rook);
+            self.put(Piece {
+                square: king_to,
+                ..piece
+            });
+            self.put(Piece {
+                square: rook_to,
+                ..rook
+            });
+        } else if let MoveKind::Drop(kind) = mv.kind {
+            self.take_from_pocket(piece.color, kind);
+            self.put(piece);
+        } else {
+            self.remove(piece);
+            self.put(Piece {
+                kind: mv.promotion().unwrap_or(piece.kind),
+                square: to,
+                ..piece
+            });
+        }
+
+        if piece.kind == PieceType::King {
+            self.castling_rights.remove(piece.color);
+        }
+        self.castling_rights.update(from, to);
+        self.en_passant_square = match mv.kind {
+            MoveKind::DoublePawnPush => Some(Square {
+                x: from.x,
+                y: (from.y + to.y) / 2,
+            }),
+            _ => None,
+        };
+        if captured.is_some() || piece.kind == PieceType::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.side_to_move == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = self.side_to_move.opponent();
+        self.hash ^= black_to_move_key();
+        self.hash ^= self.en_passant_hash() ^ castling_key(self.castling_rights);
+        if self.variant == Variant::ThreeCheck && self.is_in_check() {
+            let count = &mut self.checks[piece.color as usize];
+            self.hash ^= checks_key(piece.color, *count);
+            *count += 1;
+            self.hash ^= checks_key(piece.color, *count);
+        }
+        undo
+    }
+
+    /// Takes back the last move made, putting back any captured piece and
+    /// the rights and clocks from before it
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let mv = undo.mv;
+        self.side_to_move = self.side_to_move.opponent();
+        if self.side_to_move == PieceColor::Black {
+            self.fullmove_number -= 1;
+        }
+        if let MoveKind::Castling(side) = mv.kind {
+            let (king_to, rook_to) = castling_squares(side, mv.from.y, self.files);
+            let king = Piece {
+                kind: PieceType::King,
+                color: self.side_to_move,
+                square: king_to,
+            };
+            let rook = Piece {
+                kind: PieceType::Rook,
+                square: rook_to,
+                ..king
+            };
+            self.remove(king);
+            self.remove(rook);
+            self.put(Piece {
+                square: mv.from,
+                ..king
+            });
+            self.put(Piece {
+                square: mv.to,
+                ..rook
+            });
+        } else if let (MoveKind::Drop(_), Some(piece)) = (mv.kind, self.piece_at(mv.to)) {
+            self.remove(piece);
+        } else if let Some(piece) = self.piece_at(mv.to) {
+            self.remove(piece);
+            let kind = match mv.kind {
+                MoveKind::Promotion(_) => PieceType::Pawn,
+                _ => piece.kind,
+            };
+            self.put(Piece {
+                kind,
+                square: mv.from,
+                ..piece
+            });
+        }
+        if let Some(captured) = undo.captured {
+            self.put(captured);
+        }
+        self.castling_rights = undo.castling_rights;
+        self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.checks = undo.checks;
+        self.pockets = undo.pockets;
+        self.promoted = undo.promoted;
+        self.hash = undo.hash;
+    }
+
+    /// Whether the king of the side to move is attacked, in a variant where
+    /// that matters
+    pub fn is_in_check(&self) -> bool {
+        self.variant.has_royal_king() && self.is_king_attacked(self.side_to_move)
+    }
+
+    /// Whether the side to move has any valid move. When it has none, the game
+    /// is over: checkmate if the king is in check, stalemate otherwise.
+    pub fn has_valid_move(&self) -> bool {
+        // Compulsory captures never leave a side without a move
+        self.pieces_of_color(self.side_to_move)
+            .squares()
+            .any(|square| !self.unrestricted_moves_from(square).is_empty())
+            || self.pockets[self.side_to_move as usize]
+                .kinds()
+                .any(|kind| !self.legal_drops(kind).is_empty())
+    }
+
+    /// Whether neither side has enough pieces left to ever checkmate: bare
+    /// kings, a single minor piece, or only bishops all on squares of the same
+    /// color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let minor_pieces = self.occupied() & !self.kinds[PieceType::King as usize];
+        let bishops = self.kinds[PieceType::Bishop as usize];
+        let knights = self.kinds[PieceType::Knight as usize];
+        if minor_pieces != minor_pieces & (bishops | knights) {
+            return false;
+        }
+        let first = match minor_pieces.first() {
+            Some(square) => square,
+            None => return true,
+        };
+        minor_pieces.count() <= 1
+            || (minor_pieces == bishops
+                && bishops
+                    .squares()
+                    .all(|square| square.is_white() == first.is_white()))
+    }
+
+    /// The Zobrist hash of the position. Positions that are the same for the
+    /// repetition rules have the same hash, whatever moves led to them.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The part of the hash for the en passant file. It only counts if a pawn
+    /// of the side to move stands ready to capture, as otherwise the position
+    /// is no different from one without a target square.
+    fn en_passant_hash(&self) -> u64 {
+        match self.en_passant_square {
+            Some(square)
+                if !(pawn_attacks(self.side_to_move.opponent(), square)
+                    & self.pieces_of(PieceType::Pawn, self.side_to_move))
+                .is_empty() =>
+            {
+                en_passant_key(square.x)
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::*;
+
+    #[test]
+    fn test_find_move_keeps_king_safe() {
+        struct Test {
+            from: Square,
+            to: Square,
+            result: bool,
+        }
+        let position = Position::new(
+            vec![
+                Piece {
+                    kind: PieceType::King,
+                    color: PieceColor::White,
+                    square: Square { x: 4, y: 7 },
+                },
+                Piece {
+                    kind: PieceType::Bishop,
+                    color: PieceColor::White,
+                    square: Square { x: 4, y: 5 },
+                },
+                // Pins the bishop to the king
+                Piece {
+                    kind: PieceType::Rook,
+                    color: PieceColor::Black,
+                    square: Square { x: 4, y: 0 },
+                },
+                // Covers the f-file
+                Piece {
+                    kind: PieceType::Rook,
+                    color: PieceColor::Black,
+                    square: Square { x: 5, y: 1 },
+                },
+                Piece {
+                    kind: PieceType::King,
+                    color: PieceColor::Black,
+                    square: Square { x: 0, y: 0 },
+                },
+            ],
+            PieceColor::White,
+            CastlingRights::default(),
+            None,
+        );
+        let tests = vec![
+            // pinned piece cannot leave the line to the king
+            Test {
+                from: Square { x: 4, y: 5 },
+                to: Square { x: 3, y: 4 },
+                result: false,
+            },
+            // king cannot walk into an attacked square
+            Test {
+                from: Square { x: 4, y: 7 },
+                to: Square { x: 5, y: 7 },
+                result: false,
+            },
+            Test {
+                from: Square { x: 4, y: 7 },
+                to: Square { x: 3, y: 7 },
+                result: true,
+            },
+            // it is not Black's turn
+            Test {
+                from: Square { x: 4, y: 0 },
+                to: Square { x: 4, y: 5 },
+                result: false,
+            },
+        ];
+        for test in tests {
+            assert_eq!(
+                position.find_move(test.from, test.to, None).is_some(),
+                test.result
+            )
+        }
+    }
+
+    #[test]
+    fn test_attackers() {
+        // Every kind attacks the squares its movements reach, with a piece in
+        // the way of the sliders
+        let from = Square { x: 3, y: 4 };
+        let blocker = Square { x: 3, y: 2 };
+        for kind in PIECE_TYPES {
+            let piece = Piece {
+                kind,
+                color: PieceColor::White,
+                square: from,
+            };
+            let pawn = Piece {
+                kind: PieceType::Pawn,
+                color: PieceColor::Black,
+                square: blocker,
+            };
+            let position = Position::new(
+                vec![piece, pawn],
+                PieceColor::White,
+                CastlingRights::NONE,
+                None,
+            )
+            .with_files(MAX_FILES);
+            let attacks = piece_attacks(piece, position.occupied());
+            for square in Square::all(MAX_FILES) {
+                assert_eq!(
+                    position.attackers(square, PieceColor::White).contains(from),
+                    attacks.contains(square),
+                    "{:?} on {} attacking {}",
+                    kind,
+                    from,
+                    square
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let position = Position::default();
+        let moves = position.legal_moves();
+        assert_eq!(moves.len(), 20);
+        assert_eq!(
+            moves
+                .iter()
+                .filter(|mv| mv.kind == MoveKind::DoublePawnPush)
+                .count(),
+            8
+        );
+        assert_eq!(
+            position.legal_moves_from(Square { x: 1, y: 7 }),
+            vec![
+                Move {
+                    from: Square { x: 1, y: 7 },
+                    to: Square { x: 0, y: 5 },
+                    kind: MoveKind::Normal,
+                },
+                Move {
+                    from: Square { x: 1, y: 7 },
+                    to: Square { x: 2, y: 5 },
+                    kind: MoveKind::Normal,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_castling() {
+        let pieces = vec![
+            Piece {
+                kind: PieceType::King,
+                color: PieceColor::White,
+                square: Square { x: 4, y: 7 },
+            },
+            Piece {
+                kind: PieceType::Rook,
+                color: PieceColor::White,
+                square: Square { x: 7, y: 7 },
+            },
+            Piece {
+                kind: PieceType::Rook,
+                color: PieceColor::White,
+                square: Square { x: 0, y: 7 },
+            },
+            // Covers d1, which the king passes over when castling queenside
+            Piece {
+                kind: PieceType::Rook,
+                color: PieceColor::Black,
+                square: Square { x: 3, y: 0 },
+            },
+            Piece {
+                kind: PieceType::King,
+                color: PieceColor::Black,
+                square: Square { x: 7, y: 0 },
+            },
+        ];
+        let king = Square { x: 4, y: 7 };
+        let kingside = Square { x: 6, y: 7 };
+        let queenside = Square { x: 2, y: 7 };
+        let position = Position::new(
+            pieces.clone(),
+            PieceColor::White,
+            CastlingRights::default(),
+            None,
+        );
+        assert!(position.find_move(king, kingside, None).is_some());
+        assert!(!position.find_move(king, queenside, None).is_some());
+
+        let mut castled = position;
+        let castling = castled.find_move(king, kingside, None).unwrap();
+        assert_eq!(castling.kind, MoveKind::Castling(CastlingSide::Kingside));
+        castled.make_move(castling).unwrap();
+        assert_eq!(
+            castled.piece_at(Square { x: 5, y: 7 }).map(|p| p.kind),
+            Some(PieceType::Rook)
+        );
+        assert_eq!(castled.castling_rights().white_queenside, None);
+
+        let position = Position::new(
+            pieces,
+            PieceColor::White,
+            CastlingRights {
+                white_kingside: None,
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(!position.find_move(king, kingside, None).is_some());
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        let square = |name: &str| name.parse::<Square>().unwrap();
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/1R4KR w KQ - 0 1").unwrap();
+
+        // The king stays on g1 and the rook comes to f1
+        let kingside = position
+            .find_move(square("g1"), square("h1"), None)
+            .unwrap();
+        assert_eq!(kingside.kind, MoveKind::Castling(CastlingSide::Kingside));
+        assert_eq!(kingside.to_string(), "g1h1");
+        let mut castled = position;
+        let undo = castled.make_move(kingside).unwrap();
+        assert_eq!(castled.to_fen(), "4k3/8/8/8/8/8/8/1R3RK1 b - - 1 1");
+        castled.unmake_move(undo);
+        assert_eq!(castled, position);
+
+        // The king can also be moved to where it lands
+        let queenside = position
+            .find_move(square("g1"), square("c1"), None)
+            .unwrap();
+        assert_eq!(queenside.kind, MoveKind::Castling(CastlingSide::Queenside));
+        assert_eq!(queenside.to_string(), "g1b1");
+        let mut castled = position;
+        castled.make_move(queenside).unwrap();
+        assert_eq!(castled.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/1R1N2KR w KQ - 0 1").unwrap();
+        assert_eq!(
+            position.check_move(square("g1"), square("c1"), None),
+            Err(IllegalMove::BlockedPath)
+        );
+        let position = Position::from_fen("4k1r1/8/8/8/8/8/8/1R4KR w KQ - 0 1").unwrap();
+        assert_eq!(
+            position.check_move(square("g1"), square("h1"), None),
+            Err(IllegalMove::CastlingThroughCheck),
+            "the king may not castle out of check, even without moving"
+        );
+    }
+
+    #[test]
+    fn test_visible_squares() {
+        let square = |name: &str| name.parse::<Square>().unwrap();
+        let position = Position::default();
+        let visible = position.visible_squares(PieceColor::White);
+        // The first four ranks, with every pawn push and knight move
+        assert_eq!(visible.count(), 32);
+        assert!(visible.contains(square("a4")) && !visible.contains(square("a5")));
+        assert_eq!(
+            position.visible_squares(PieceColor::Black).count(),
+            32,
+            "the side not to move sees as far"
+        );
+
+        let position = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let visible = position.visible_squares(PieceColor::White);
+        assert!(
+            visible.contains(square("d5")),
+            "the pawn to take en passant"
+        );
+        assert!(!visible.contains(square("e8")));
+        let visible = position.visible_squares(PieceColor::Black);
+        assert!(!visible.contains(square("e5")), "no black piece reaches e5");
+        assert!(visible.contains(square("d4")));
+
+        // The pawn on e5 is blocked by a king White cannot see, which the
+        // reason for rejecting the push would give away
+        let position = Position::from_fen("8/8/4k3/4P3/8/8/8/4K3 w - - 0 1").unwrap();
+        let visible = position.visible_squares(PieceColor::White);
+        assert!(!visible.contains(square("e6")));
+        let reason = position.check_move(square("e5"), square("e6"), None);
+        assert_eq!(reason, Err(IllegalMove::BlockedPath));
+        assert!(IllegalMove::BlockedPath.depends_on_opponent());
+        assert!(!IllegalMove::PawnBackwards.depends_on_opponent());
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let pieces = vec![
+            Piece {
+                kind: PieceType::Pawn,
+                color: PieceColor::White,
+                square: Square { x: 4, y: 3 },
+            },
+            Piece {
+                kind: PieceType::Pawn,
+                color: PieceColor::Black,
+                square: Square { x: 3, y: 3 },
+            },
+        ];
+        let from = Square { x: 4, y: 3 };
+        let target = Square { x: 3, y: 2 };
+        let rights = CastlingRights::default();
+        let position = Position::new(pieces.clone(), PieceColor::White, rights, None);
+        assert!(!position.find_move(from, target, None).is_some());
+
+        let mut position = Position::new(pieces, PieceColor::White, rights, Some(target));
+        let en_passant = position.find_move(from, target, None).unwrap();
+        assert_eq!(en_passant.kind, MoveKind::EnPassant);
+        position.make_move(en_passant).unwrap();
+        assert_eq!(position.piece_at(Square { x: 3, y: 3 }), None);
+        assert_eq!(position.pieces().len(), 1);
+    }
+
+    #[test]
+    fn test_illegal_move_reasons() {
+        struct Test {
+            fen: &'static str,
+            from: &'static str,
+            to: &'static str,
+            promotion: Option<PieceType>,
+            reason: IllegalMove,
+        }
+        let test = |fen, from, to, reason| Test {
+            fen,
+            from,
+            to,
+            promotion: None,
+            reason,
+        };
+        let tests = vec![
+            test(START_FEN, "e4", "e5", IllegalMove::NoPiece),
+            test(START_FEN, "e7", "e5", IllegalMove::NotYourTurn),
+            test(START_FEN, "b1", "b3", IllegalMove::CannotMoveThere),
+            test(START_FEN, "e2", "e5", IllegalMove::CannotMoveThere),
+            test(START_FEN, "a1", "a3", IllegalMove::BlockedPath),
+            test(START_FEN, "g1", "e2", IllegalMove::OwnPieceOnTarget),
+            test(START_FEN, "e2", "d3", IllegalMove::NothingToCapture),
+            test(
+                "4k3/8/8/8/8/4P3/8/4K3 w - - 0 1",
+                "e3",
+                "e2",
+                IllegalMove::PawnBackwards,
+            ),
+            test(
+                "4k3/8/8/8/8/4P3/8/4K3 w - - 0 1",
+                "e3",
+                "e5",
+                IllegalMove::CannotMoveThere,
+            ),
+            test(
+                "4k3/8/8/8/8/4p3/4P3/4K3 w - - 0 1",
+                "e2",
+                "e4",
+                IllegalMove::BlockedPath,
+            ),
+            // The bishop is pinned
+            test(
+                "4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1",
+                "e2",
+                "d3",
+                IllegalMove::KingInCheck,
+            ),
+            test(
+                "4k3/8/8/8/8/8/3r4/4K3 w - - 0 1",
+                "e1",
+                "d1",
+                IllegalMove::KingInCheck,
+            ),
+            test(
+                "r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1",
+                "e1",
+                "g1",
+                IllegalMove::CastlingRightLost,
+            ),
+            test(
+                "r3k2r/8/8/8/8/8/8/R3KB1R w KQkq - 0 1",
+                "e1",
+                "g1",
+                IllegalMove::BlockedPath,
+            ),
+            test(
+                "r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1",
+                "e1",
+                "g1",
+                IllegalMove::CastlingThroughCheck,
+            ),
+            test(
+                "8/1P5k/8/8/8/8/8/K7 w - - 0 1",
+                "b7",
+                "b8",
+                IllegalMove::PromotionNeeded,
+            ),
+            Test {
+                promotion: Some(PieceType::Queen),
+                ..test(START_FEN, "e2", "e4", IllegalMove::InvalidPromotion)
+            },
+        ];
+        for test in tests {
+            let position = Position::from_fen(test.fen).unwrap();
+            let (from, to) = (test.from.parse().unwrap(), test.to.parse().unwrap());
+            assert_eq!(
+                position.check_move(from, to, test.promotion),
+                Err(test.reason),
+                "{} {}{}",
+                test.fen,
+                test.from,
+                test.to
+            );
+        }
+    }
+
+    #[test]
+    fn test_promotion() {
+        let from = Square { x: 0, y: 1 };
+        let to = Square { x: 0, y: 0 };
+        let mut position = Position::new(
+            vec![Piece {
+                kind: PieceType::Pawn,
+                color: PieceColor::White,
+                square: from,
+            }],
+            PieceColor::White,
+            CastlingRights::default(),
+            None,
+        );
+        assert_eq!(position.legal_moves().len(), 4);
+        assert_eq!(position.find_move(from, to, None), None);
+        assert_eq!(position.find_move(from, to, Some(PieceType::King)), None);
+        let mv = Move {
+            from,
+            to,
+            kind: MoveKind::Normal,
+        };
+        assert_eq!(position.make_move(mv), Err(IllegalMove::PromotionNeeded));
+        let mv = position
+            .find_move(from, to, Some(PieceType::Knight))
+            .unwrap();
+        position.make_move(mv).unwrap();
+        assert_eq!(
+            position.piece_at(to).map(|p| p.kind),
+            Some(PieceType::Knight)
+        );
+    }
+
+    #[test]
+    fn test_has_valid_move() {
+        let black_king = Piece {
+            kind: PieceType::King,
+            color: PieceColor::Black,
+            square: Square { x: 0, y: 0 },
+        };
+        let white_king = Piece {
+            kind: PieceType::King,
+            color: PieceColor::White,
+            square: Square { x: 2, y: 1 },
+        };
+        let position_with_queen_on = |square| {
+            Position::new(
+                vec![
+                    black_king,
+                    white_king,
+                    Piece {
+                        kind: PieceType::Queen,
+                        color: PieceColor::White,
+                        square,
+                    },
+                ],
+                PieceColor::Black,
+                CastlingRights::default(),
+                None,
+            )
+        };
+        // Stalemate: the queen covers every square the king could go to
+        let position = position_with_queen_on(Square { x: 1, y: 2 });
+        assert!(!position.has_valid_move());
+        assert!(!position.is_in_check());
+        // Checkmate: the queen now attacks the king too, and is protected
+        let position = position_with_queen_on(Square { x: 1, y: 1 });
+        assert!(!position.has_valid_move());
+        assert!(position.is_in_check());
+    }
+
+    #[test]
+    fn test_is_insufficient_material() {
+        let with = |extra: Vec<(PieceType, PieceColor, Square)>| {
+            let mut pieces = vec![
+                Piece {
+                    kind: PieceType::King,
+                    color: PieceColor::White,
+                    square: Square { x: 4, y: 7 },
+                },
+                Piece {
+                    kind: PieceType::King,
+                    color: PieceColor::Black,
+                    square: Square { x: 4, y: 0 },
+                },
+            ];
+            for (kind, color, square) in extra {
+                pieces.push(Piece {
+                    kind,
+                    color,
+                    square,
+                });
+            }
+            Position::new(pieces, PieceColor::White, CastlingRights::default(), None)
+                .is_insufficient_material()
+        };
+        assert!(with(vec![]));
+        assert!(with(vec![(
+            PieceType::Knight,
+            PieceColor::White,
+            Square { x: 1, y: 7 }
+        )]));
+        // Bishops on squares of the same color
+        assert!(with(vec![
+            (PieceType::Bishop, PieceColor::White, Square { x: 2, y: 7 }),
+            (PieceType::Bishop, PieceColor::Black, Square { x: 5, y: 0 }),
+        ]));
+        // Bishops on squares of different colors
+        assert!(!with(vec![
+            (PieceType::Bishop, PieceColor::White, Square { x: 2, y: 7 }),
+            (PieceType::Bishop, PieceColor::Black, Square { x: 2, y: 0 }),
+        ]));
+        assert!(!with(vec![(
+            PieceType::Pawn,
+            PieceColor::White,
+            Square { x: 1, y: 6 }
+        )]));
+    }
+
+    #[test]
+    fn test_hash() {
+        let play = |position: &mut Position, (from_x, from_y), (to_x, to_y)| {
+            let from = Square {
+                x: from_x,
+                y: from_y,
+            };
+            let to = Square { x: to_x, y: to_y };
+            let mv = position.find_move(from, to, None).unwrap();
+            position.make_move(mv).unwrap();
+        };
+        let from_scratch = |position: &Position| {
+            Position::new(
+                position.pieces(),
+                position.side_to_move(),
+                position.castling_rights(),
+                position.en_passant_square(),
+            )
+            .hash()
+        };
+
+        // The knights going out and back transpose to the start
+        let mut position = Position::default();
+        play(&mut position, (6, 7), (5, 5));
+        play(&mut position, (6, 0), (5, 2));
+        play(&mut position, (5, 5), (6, 7));
+        play(&mut position, (5, 2), (6, 0));
+        assert_eq!(position.hash(), Position::default().hash());
+
+        // No black pawn can take on e3, so the target square does not count
+        let mut position = Position::default();
+        play(&mut position, (4, 6), (4, 4));
+        let mut without_target = Position::new(
+            position.pieces(),
+            PieceColor::Black,
+            CastlingRights::default(),
+            None,
+        );
+        assert_eq!(position.hash(), without_target.hash());
+        // The kings walking out and back lose their castling rights
+        play(&mut position, (4, 1), (4, 3));
+        play(&mut without_target, (4, 1), (4, 3));
+        play(&mut without_target, (4, 7), (4, 6));
+        play(&mut without_target, (4, 0), (4, 1));
+        play(&mut without_target, (4, 6), (4, 7));
+        play(&mut without_target, (4, 1), (4, 0));
+        assert_eq!(position.pieces(), without_target.pieces());
+        assert_ne!(position.hash(), without_target.hash());
+
+        // The incremental hash matches the one computed from scratch
+        let mut position = Position::default();
+        for ply in 0..200 {
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            position.make_move_unchecked(moves[(ply * 7) % moves.len()]);
+            assert_eq!(position.hash(), from_scratch(&position));
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Standard Algebraic Notation, the way moves are written in PGN and on score
+//! sheets: the piece letter, just enough of the starting square to tell it
+//! apart from other pieces that could make the same move, `x` for captures,
+//! the target square, and `+` or `#` after checks and checkmates. Crazyhouse
+//! drops are written as the piece, an `@` and the square, like `N@f3`.
+
+use std::fmt;
+
+use crate::moves::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SanError {
+    /// The text is not written like a move
+    Invalid(String),
+    /// No legal move matches the text
+    Illegal(String),
+    /// More than one legal move matches the text
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::Invalid(san) => write!(f, "'{}' is not a move", san),
+            SanError::Illegal(san) => write!(f, "{} is not a legal move", san),
+            SanError::Ambiguous(san) => {
+                write!(f, "{} could be more than one move", san)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+impl Position {
+    /// Writes a legal move in SAN
+    pub fn to_san(&self, mv: Move) -> String {
+        let mut san = match mv.kind {
+            MoveKind::Castling(CastlingSide::Kingside) => "O-O".to_string(),
+            MoveKind::Castling(CastlingSide::Queenside) => "O-O-O".to_string(),
+            MoveKind::Drop(kind) => format!("{}@{}", kind.letter(), mv.to),
+            _ => {
+                let piece = match self.piece_at(mv.from) {
+                    Some(piece) => piece,
+                    None => return String::new(),
+                };
+                let is_capture = mv.kind == MoveKind::EnPassant || self.piece_at(mv.to).is_some();
+                let mut san = String::new();
+                if piece.kind == PieceType::Pawn {
+                    if is_capture {
+                        san.push((b'a' + mv.from.x) as char);
+                    }
+                } else {
+                    san.push(piece.kind.letter());
+                    san += &self.disambiguation(piece, mv);
+                }
+                if is_capture {
+                    san.push('x');
+                }
+                san += &mv.to.to_string();
+                if let Some(kind) = mv.promotion() {
+                    san.push('=');
+                    san.push(kind.letter());
+                }
+                san
+            }
+        };
+
+        let mut next = *self;
+        next.make_move_unchecked(mv);
+        if next.is_in_check() {
+            san.push(if next.has_valid_move() { '+' } else { '#' });
+        }
+        san
+    }
+
+    /// The part of the starting square needed to tell the move apart from
+    /// moves to the same square by other pieces of the same kind: the file if
+    /// that is enough, otherwise the rank, otherwise both
+    fn disambiguation(&self, piece: Piece, mv: Move) -> String {
+        let others: Vec<Move> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && self.piece_at(other.from).map(|p| p.kind) == Some(piece.kind)
+            })
+            .collect();
+        let name = mv.from.to_string();
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| other.from.x != mv.from.x) {
+            name[..1].to_string()
+        } else if others.iter().all(|other| other.from.y != mv.from.y) {
+            name[1..].to_string()
+        } else {
+            name
+        }
+    }
+
+    /// Reads a move written in SAN. Check and mate suffixes and annotations
+    /// like `!?` are optional, and castling may be written with zeros.
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let invalid = || SanError::Invalid(san.to_string());
+        let text = san.trim_end_matches(|c| "+#!?".contains(c));
+
+        let castling = match text {
+            "O-O" | "0-0" => Some(CastlingSide::Kingside),
+            "O-O-O" | "0-0-0" => Some(CastlingSide::Queenside),
+            _ => None,
+        };
+        if let Some(side) = castling {
+            return self
+                .legal_moves()
+                .into_iter()
+                .find(|mv| mv.kind == MoveKind::Castling(side))
+                .ok_or_else(|| SanError::Illegal(san.to_string()));
+        }
+        // Pawn drops may leave out the letter
+        if let Some(at) = text.find('@') {
+            let kind = match &text[..at] {
+                "" => PieceType::Pawn,
+                letter => match letter.chars().next().and_then(PieceType::from_letter) {
+                    Some(kind) if letter.len() == 1 => kind,
+                    _ => return Err(invalid()),
+                },
+            };
+            let to: Square = text[at + 1..].parse().map_err(|_| invalid())?;
+            return self
+                .check_drop(kind, to)
+                .map_err(|_| SanError::Illegal(san.to_string()));
+        }
+
+        let mut chars: Vec<char> = text.chars().collect();
+        let kind = match chars.first() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let kind = match PieceType::from_letter(*c) {
+                    Some(kind) if kind != PieceType::Pawn => kind,
+                    _ => return Err(invalid()),
+                };
+                chars.remove(0);
+                kind
+            }
+            _ => PieceType::Pawn,
+        };
+        // The promotion comes last, usually after an equals sign
+        let promotion = match chars.last() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let kind = match PieceType::from_letter(*c) {
+                    Some(kind) if self.variant().promotion_pieces().contains(&kind) => kind,
+                    _ => return Err(invalid()),
+                };
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                Some(kind)
+            }
+            _ => None,
+        };
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let target: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to: Square = target.parse().map_err(|_| invalid())?;
+        if chars.last() == Some(&'x') {
+            chars.pop();
+        }
+        // Whatever is left narrows down the starting square
+        let last_file = (b'a' + MAX_FILES - 1) as char;
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in chars {
+            match c {
+                _ if ('a'..=last_file).contains(&c) && from_file.is_none() => {
+                    from_file = Some(c as u8 - b'a')
+                }
+                '1'..='8' if from_rank.is_none() => from_rank = Some(b'8' - c as u8),
+                _ => return Err(invalid()),
+            }
+        }
+
+        let mut matches = self.legal_moves().into_iter().filter(|mv| {
+            mv.to == to
+                && mv.promotion() == promotion
+                && !matches!(mv.kind, MoveKind::Castling(_))
+                && self.piece_at(mv.from).map(|p| p.kind) == Some(kind)
+                && from_file.map_or(true, |x| mv.from.x == x)
+                && from_rank.map_or(true, |y| mv.from.y == y)
+        });
+        match (matches.next(), matches.next()) {
+            (Some(mv), None) => Ok(mv),
+            (None, _) => Err(SanError::Illegal(san.to_string())),
+            (Some(_), Some(_)) => Err(SanError::Ambiguous(san.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::*;
+    use crate::san::*;
+
+    #[test]
+    fn test_san() {
+        struct Test {
+            fen: &'static str,
+            san: &'static str,
+        }
+        let tests = vec![
+            Test {
+                fen: START_FEN,
+                san: "e4",
+            },
+            Test {
+                fen: START_FEN,
+                san: "Nf3",
+            },
+            // Knights on b1 and f3 can both reach d2
+            Test {
+                fen: "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1",
+                san: "Nbd2",
+            },
+            // Rooks on a1 and a5 can both reach a3
+            Test {
+                fen: "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1",
+                san: "R1a3",
+            },
+            // Queens on e4, h4 and h1 can all reach e1
+            Test {
+                fen: "8/8/k7/8/4Q2Q/8/8/K6Q w - - 0 1",
+                san: "Qh4e1",
+            },
+            Test {
+                fen: "r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1",
+                san: "O-O-O",
+            },
+            Test {
+                fen: "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+                san: "exd6",
+            },
+            Test {
+                fen: "8/1P5k/8/8/8/8/8/K7 w - - 0 1",
+                san: "b8=Q",
+            },
+            Test {
+                fen: "r6k/1P6/8/8/8/8/8/K7 w - - 0 1",
+                san: "bxa8=N",
+            },
+            Test {
+                fen: "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+                san: "Ra8+",
+            },
+            // Fool's mate
+            Test {
+                fen: "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+                san: "Qh4#",
+            },
+        ];
+        for test in tests {
+            let position = Position::from_fen(test.fen).unwrap();
+            let mv = position.parse_san(test.san).unwrap();
+            assert_eq!(position.to_san(mv), test.san);
+        }
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let position = Position::default();
+        let e4 = position.parse_san("e4").unwrap();
+        assert_eq!(e4.kind, MoveKind::DoublePawnPush);
+        assert_eq!(position.parse_san("e4!?"), Ok(e4));
+        assert_eq!(
+            position.parse_san("e5"),
+            Err(SanError::Illegal("e5".to_string()))
+        );
+        assert_eq!(
+            position.parse_san("Ke9"),
+            Err(SanError::Invalid("Ke9".to_string()))
+        );
+        let position = Position::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(
+            position.parse_san("Nd2"),
+            Err(SanError::Ambiguous("Nd2".to_string()))
+        );
+        let position = Position::from_fen("8/1P5k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(
+            position.parse_san("b8Q").map(|mv| mv.promotion()),
+            Ok(Some(PieceType::Queen))
+        );
+        assert!(position.parse_san("b8").is_err());
+
+        // Files past h on a wider board, written and read back
+        let mut position = Position::from_fen(CAPABLANCA_FEN).unwrap();
+        for san in ["i4", "h5"] {
+            let mv = position.parse_san(san).unwrap();
+            position.make_move(mv).unwrap();
+        }
+        let capture = position.parse_san("ixh5").unwrap();
+        assert_eq!(capture.from, "i4".parse().unwrap());
+        assert_eq!(position.to_san(capture), "ixh5");
+        assert_eq!(position.parse_san(&position.to_san(capture)), Ok(capture));
+    }
+}
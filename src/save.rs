@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use chess::game::*;
+use chess::pgn::*;
+
+/// The folder games played in the window are saved to
+const SAVE_DIRECTORY: &str = "games";
+
+/// Where the game being played is saved, named after the time it started
+struct SaveFile {
+    path: PathBuf,
+    date: String,
+}
+
+impl Default for SaveFile {
+    fn default() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+        let time = seconds % 86400;
+        let name = format!(
+            "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.pgn",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        );
+        SaveFile {
+            path: PathBuf::from(SAVE_DIRECTORY).join(name),
+            date: format!("{:04}.{:02}.{:02}", year, month, day),
+        }
+    }
+}
+
+/// The (year, month, day) of a number of days since 1970-01-01, using
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Writes the game to its PGN file after every move, so that every game
+/// played is kept, finished or not. A game loaded at launch is only saved
+/// once it is played on.
+fn save_game(game: Res<Game>, save_file: Res<SaveFile>) {
+    if !game.is_changed() || game.is_added() || game.moves().is_empty() {
+        return;
+    }
+    let mut pgn = PgnGame::from_game(&game);
+    pgn.set_tag("Event", "Casual game");
+    pgn.set_tag("Date", &save_file.date);
+    let result =
+        fs::create_dir_all(SAVE_DIRECTORY).and_then(|_| fs::write(&save_file.path, pgn.to_pgn()));
+    if let Err(error) = result {
+        eprintln!("Could not save {}: {}", save_file.path.display(), error);
+    }
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SaveFile>()
+            .add_system(save_game.system());
+    }
+}
@@ -0,0 +1,502 @@
+//! Looks ahead for the best move with negamax and alpha-beta pruning. Every
+//! line is followed a fixed number of moves, then on through captures only,
+//! so a line never stops in the middle of an exchange.
+//!
+//! Alpha-beta cuts off more of the tree the sooner it tries good moves, so
+//! they are ordered: captures first, most valuable victim and then least
+//! valuable attacker first (MVV-LVA), then the killer moves, quiet moves that
+//! refuted another line at the same distance from the root, and last the rest
+//! by their history, how often and how deep they have refuted lines before.
+//!
+//! The search deepens one move at a time, each time trying the best move of
+//! the last depth first, until it reaches its limits. What each depth learns
+//! makes the next one quicker, and stopping at any point leaves the best move
+//! of the last depth finished to play.
+
+use std::cmp::{max, min, Reverse};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::evaluation::*;
+use crate::game::*;
+use crate::limits::*;
+use crate::moves::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+/// Scores beyond this are mates, found this many moves from the root or fewer
+const MATE_BOUND: i32 = MATE - 1000;
+
+/// The deepest a search goes without a limit on depth
+const MAX_DEPTH: u32 = 64;
+
+/// How many positions are visited between looks at the clock, a power of two
+/// so that counting them is a mask
+const NODES_BETWEEN_CHECKS: u64 = 1024;
+
+/// A search from the current position of a game, which keeps what it learns
+/// about move ordering from one search to the next
+pub struct Search {
+    position: Position,
+    /// The hashes of the positions before the one being searched, from the
+    /// game and then down the line being searched, for spotting repetitions
+    path: Vec<u64>,
+    /// Two quiet moves for each distance from the root that caused a cutoff
+    killers: Vec<[Option<Move>; 2]>,
+    /// A score for each quiet move of each side from one square to another,
+    /// raised every time it causes a cutoff
+    history: Vec<u32>,
+    nodes: u64,
+    /// The depth of the last iteration that finished
+    depth: u32,
+    /// Set from anywhere to stop searching
+    stop: Arc<AtomicBool>,
+    /// Whether the iteration under way has been abandoned
+    aborted: bool,
+    node_limit: Option<u64>,
+    time_manager: TimeManager,
+}
+
+impl Search {
+    pub fn new(game: &Game) -> Search {
+        let hashes = game.hashes();
+        Search {
+            position: *game.position(),
+            path: hashes[..hashes.len() - 1].to_vec(),
+            killers: Vec::new(),
+            history: vec![0; 2 * SQUARES * SQUARES],
+            nodes: 0,
+            depth: 0,
+            stop: Arc::new(AtomicBool::new(false)),
+            aborted: false,
+            node_limit: None,
+            time_manager: TimeManager::new(&Limits::default(), game.position().side_to_move()),
+        }
+    }
+
+    /// The number of positions visited so far
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// How many moves ahead the last search looked in full
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// A flag to set from another thread to stop searching. The search then
+    /// returns the best move of the last depth it finished, and later ones stop
+    /// after the first depth.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// The best move found within the limits, with its score for the side to
+    /// move, or nothing if the game is over. However soon it is stopped, the
+    /// search looks at least one move ahead, so there is always a move to play.
+    pub fn search(&mut self, limits: &Limits) -> Option<(Move, i32)> {
+        let position = self.position;
+        self.time_manager = TimeManager::new(limits, position.side_to_move());
+        self.node_limit = limits.nodes.map(|nodes| self.nodes + nodes);
+        self.aborted = false;
+        self.depth = 0;
+
+        let mut moves = position.legal_moves();
+        self.order_moves(&position, &mut moves, 0);
+        let mut best = None;
+        for depth in 1..=max(limits.depth.unwrap_or(MAX_DEPTH), 1) {
+            let (mv, score) = match self.search_root(&mut moves, depth) {
+                Some(result) => result,
+                None => break,
+            };
+            best = Some((mv, score));
+            self.depth = depth;
+            // Looking further cannot find a quicker mate
+            let mate_found = is_mate_score(score) && MATE - score.abs() <= depth as i32;
+            if mate_found || !self.time_manager.can_deepen() {
+                break;
+            }
+        }
+        best
+    }
+
+    /// The best of the moves looking `depth` moves ahead, which is then moved
+    /// to the front, or nothing if the search is stopped before it is known
+    fn search_root(&mut self, moves: &mut [Move], depth: u32) -> Option<(Move, i32)> {
+        let position = self.position;
+        let mut alpha = -MATE;
+        let mut best = None;
+        self.path.push(position.hash());
+        for (index, mv) in moves.iter().enumerate() {
+            let mut next = position;
+            next.make_move_unchecked(*mv);
+            let score = -self.negamax(&next, depth - 1, 1, -MATE, -alpha);
+            if self.aborted {
+                break;
+            }
+            if best.is_none() || score > alpha {
+                alpha = score;
+                best = Some((index, score));
+            }
+        }
+        self.path.pop();
+        if self.aborted {
+            return None;
+        }
+        let (index, score) = best?;
+        moves[..=index].rotate_right(1);
+        Some((moves[0], score))
+    }
+
+    /// Gives up on the iteration under way once the search has used up its
+    /// time or positions or been asked to stop, and tells whether it has. Only
+    /// the first iteration is never given up on.
+    fn check_abort(&mut self) -> bool {
+        if !self.aborted && self.depth > 0 {
+            let out_of_nodes = matches!(self.node_limit, Some(limit) if self.nodes >= limit);
+            let interrupted = self.nodes & (NODES_BETWEEN_CHECKS - 1) == 0
+                && (self.stop.load(Ordering::Relaxed) || self.time_manager.is_out_of_time());
+            self.aborted = out_of_nodes || interrupted;
+        }
+        self.aborted
+    }
+
+    fn negamax(
+        &mut self,
+        position: &Position,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.check_abort() {
+            return 0;
+        }
+        self.nodes += 1;
+        if let Some(score) = self.end_score(position, ply) {
+            return score;
+        }
+        if depth == 0 {
+            return self.quiesce(position, ply, alpha, beta);
+        }
+        let mut moves = position.legal_moves();
+        if moves.is_empty() {
+            return no_moves_score(position, ply);
+        }
+        self.order_moves(position, &mut moves, ply);
+
+        self.path.push(position.hash());
+        for mv in moves {
+            let mut next = *position;
+            next.make_move_unchecked(mv);
+            let score = -self.negamax(&next, depth - 1, ply + 1, -beta, -alpha);
+            if score >= beta {
+                if !position.is_capture(mv) {
+                    self.remember_cutoff(position, mv, depth, ply);
+                }
+                self.path.pop();
+                return beta;
+            }
+            alpha = max(alpha, score);
+        }
+        self.path.pop();
+        alpha
+    }
+
+    /// Searches captures until none are left or none are worth making, as the
+    /// side to move may also stand pat and not capture at all. In check
+    /// there is no standing pat, so every move is tried.
+    fn quiesce(&mut self, position: &Position, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        if self.check_abort() {
+            return 0;
+        }
+        self.nodes += 1;
+        if let Some(score) = self.end_score(position, ply) {
+            return score;
+        }
+        let mut moves = position.legal_moves();
+        let in_check = position.is_in_check();
+        if in_check {
+            if moves.is_empty() {
+                return no_moves_score(position, ply);
+            }
+        } else {
+            let stand_pat = evaluate(position);
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = max(alpha, stand_pat);
+            moves.retain(|mv| position.is_capture(*mv));
+        }
+        self.order_moves(position, &mut moves, ply);
+
+        self.path.push(position.hash());
+        for mv in moves {
+            let mut next = *position;
+            next.make_move_unchecked(mv);
+            let score = -self.quiesce(&next, ply + 1, -beta, -alpha);
+            if score >= beta {
+                self.path.pop();
+                return beta;
+            }
+            alpha = max(alpha, score);
+        }
+        self.path.pop();
+        alpha
+    }
+
+    /// The score of a position the game cannot go on from: a win under the
+    /// variant's rules for the side that just moved, or a draw by repetition,
+    /// the fifty-move rule or a lack of material
+    fn end_score(&self, position: &Position, ply: u32) -> Option<i32> {
+        let variant = position.variant();
+        if variant.result(position).is_some() {
+            return Some(-mate_in(ply));
+        }
+        // Repeating a position once is enough to count as a draw, as either
+        // side could repeat it again
+        let reversible = min(position.halfmove_clock() as usize, self.path.len());
+        let repeated = self.path[self.path.len() - reversible..]
+            .iter()
+            .any(|hash| *hash == position.hash());
+        if repeated
+            || position.halfmove_clock() >= 100
+            || variant.is_insufficient_material(position)
+        {
+            return Some(0);
+        }
+        None
+    }
+
+    /// Puts the moves most likely to cause a cutoff first
+    fn order_moves(&self, position: &Position, moves: &mut [Move], ply: u32) {
+        let killers = self.killers.get(ply as usize).copied().unwrap_or([None; 2]);
+        moves.sort_by_cached_key(|mv| {
+            let promotion = mv.promotion().map_or(0, piece_value);
+            let score = if position.is_capture(*mv) {
+                3_000_000 + mvv_lva(position, *mv) + promotion
+            } else if promotion > 0 {
+                2_000_000 + promotion
+            } else if killers[0] == Some(*mv) {
+                1_000_002
+            } else if killers[1] == Some(*mv) {
+                1_000_001
+            } else {
+                min(
+                    self.history[history_index(position.side_to_move(), *mv)],
+                    1_000_000,
+                ) as i32
+            };
+            Reverse(score)
+        });
+    }
+
+    /// Learns from a quiet move that was too good for the opponent to allow
+    fn remember_cutoff(&mut self, position: &Position, mv: Move, depth: u32, ply: u32) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+        // Deeper cutoffs save more work, so they count for more
+        let score = &mut self.history[history_index(position.side_to_move(), mv)];
+        *score = score.saturating_add(depth * depth);
+    }
+}
+
+/// The score for being mated, or mating, that many moves from the root, so
+/// quicker mates score higher
+fn mate_in(ply: u32) -> i32 {
+    MATE - ply as i32
+}
+
+/// Whether the score is a forced mate for one side or the other
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE_BOUND
+}
+
+/// The score for the side to move when it has no legal move, depending on
+/// what that means in the variant
+fn no_moves_score(position: &Position, ply: u32) -> i32 {
+    match position.variant().no_moves_result(position).outcome {
+        Outcome::Win(color) if color == position.side_to_move() => mate_in(ply),
+        Outcome::Win(_) => -mate_in(ply),
+        Outcome::Draw => 0,
+    }
+}
+
+/// Most valuable victim first, then least valuable attacker
+fn mvv_lva(position: &Position, mv: Move) -> i32 {
+    let victim = match mv.kind {
+        MoveKind::EnPassant => PieceType::Pawn,
+        _ => position
+            .piece_at(mv.to)
+            .map_or(PieceType::Pawn, |piece| piece.kind),
+    };
+    let attacker = position
+        .piece_at(mv.from)
+        .map_or(PieceType::Pawn, |piece| piece.kind);
+    piece_value(victim) * 16 - piece_value(attacker)
+}
+
+fn history_index(color: PieceColor, mv: Move) -> usize {
+    (color as usize * SQUARES + mv.from.index()) * SQUARES + mv.to.index()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::search::*;
+    use crate::variant::*;
+
+    #[test]
+    fn test_search() {
+        struct Test {
+            name: &'static str,
+            position: Position,
+            depth: u32,
+            best_move: &'static str,
+            score: fn(i32) -> bool,
+        }
+        let fen = |fen| Position::from_fen(fen).unwrap();
+        let tests = vec![
+            Test {
+                name: "back rank mate",
+                position: fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1"),
+                depth: 2,
+                best_move: "a1a8",
+                score: |score| score == mate_in(1),
+            },
+            Test {
+                name: "hanging queen",
+                position: fen("4k3/8/8/3q4/8/2N5/7P/4K3 w - - 0 1"),
+                depth: 2,
+                best_move: "c3d5",
+                score: |score| score > 0,
+            },
+            // Almost every move stalemates Black
+            Test {
+                name: "avoid stalemate",
+                position: fen("k7/P7/1K6/8/8/8/8/7R w - - 0 1"),
+                depth: 3,
+                best_move: "h1h8",
+                score: |score| is_mate_score(score),
+            },
+            Test {
+                name: "king of the hill",
+                position: fen("4k3/8/8/8/8/5K2/8/8 w - - 0 1").with_variant(Variant::KingOfTheHill),
+                depth: 1,
+                best_move: "f3e4",
+                score: |score| score == mate_in(1),
+            },
+        ];
+        for test in tests {
+            let mut search = Search::new(&Game::new(test.position));
+            let (mv, score) = search.search(&Limits::depth(test.depth)).unwrap();
+            assert_eq!(mv.to_string(), test.best_move, "{}", test.name);
+            assert!((test.score)(score), "{} scores {}", test.name, score);
+            assert!(search.nodes() > 0);
+        }
+
+        let checkmate = fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert_eq!(
+            Search::new(&Game::new(checkmate)).search(&Limits::depth(3)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        struct Test {
+            name: &'static str,
+            limits: Limits,
+            /// Whether the depth reached is right
+            depth: fn(u32) -> bool,
+            /// Whether the positions visited are right
+            nodes: fn(u64) -> bool,
+        }
+        let mut out_of_time = Limits::default();
+        out_of_time.time[PieceColor::White as usize] = Some(Duration::ZERO);
+        let tests = vec![
+            Test {
+                name: "fixed depth",
+                limits: Limits::depth(3),
+                depth: |depth| depth == 3,
+                nodes: |_| true,
+            },
+            Test {
+                name: "fixed nodes",
+                limits: Limits::nodes(5_000),
+                depth: |depth| (2..MAX_DEPTH).contains(&depth),
+                nodes: |nodes| nodes <= 5_000,
+            },
+            Test {
+                name: "fixed time",
+                limits: Limits::movetime(Duration::from_millis(20)),
+                depth: |depth| (1..MAX_DEPTH).contains(&depth),
+                nodes: |_| true,
+            },
+            // The first depth is always finished, to have a move to play
+            Test {
+                name: "no time left",
+                limits: out_of_time,
+                depth: |depth| depth == 1,
+                nodes: |nodes| nodes > 0,
+            },
+        ];
+        for test in tests {
+            let mut search = Search::new(&Game::default());
+            assert!(search.search(&test.limits).is_some(), "{}", test.name);
+            assert!(
+                (test.depth)(search.depth()),
+                "{} reaches depth {}",
+                test.name,
+                search.depth()
+            );
+            assert!(
+                (test.nodes)(search.nodes()),
+                "{} visits {}",
+                test.name,
+                search.nodes()
+            );
+        }
+
+        // A mate needs looking no further, even without limits
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let mut search = Search::new(&Game::new(position));
+        let (mv, _score) = search.search(&Limits::default()).unwrap();
+        assert_eq!(mv.to_string(), "a1a8");
+        assert_eq!(search.depth(), 1);
+
+        // Stopping also ends a search without limits
+        let mut search = Search::new(&Game::default());
+        let stop = search.stop_flag();
+        let thread = std::thread::spawn(move || {
+            let best = search.search(&Limits::default());
+            (best, search.depth())
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        stop.store(true, Ordering::Relaxed);
+        let (best, depth) = thread.join().unwrap();
+        assert!(best.is_some());
+        assert!(depth < MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_move_ordering() {
+        let position = Position::from_fen("7k/8/8/3q4/2P5/8/8/3RK3 w - - 0 1").unwrap();
+        let search = Search::new(&Game::new(position));
+        let mut moves = position.legal_moves();
+        search.order_moves(&position, &mut moves, 0);
+        // The pawn takes the queen before the rook does
+        assert_eq!(moves[0].to_string(), "c4d5");
+        assert_eq!(moves[1].to_string(), "d1d5");
+        assert!(!position.is_capture(moves[2]));
+    }
+}
@@ -0,0 +1,307 @@
+use std::cmp::{max, min};
+use std::fmt;
+use std::str::FromStr;
+
+/// The most files a board can have, which is as many as a bitboard has room
+/// for with eight ranks
+pub const MAX_FILES: u8 = 16;
+
+/// The number of ranks of every board, however many files it has
+pub const RANKS: u8 = 8;
+
+/// The number of squares on the widest board
+pub const SQUARES: usize = MAX_FILES as usize * RANKS as usize;
+
+/// A square of the board. `x` is the file, 0 being the a-file, and `y` counts
+/// down the ranks from Black's side, so a8 is (0, 0) and h1 is (7, 7).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Square {
+    pub x: u8,
+    pub y: u8,
+}
+
+/// The square in algebraic notation, like `e4`
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.x) as char, RANKS - self.y)
+    }
+}
+
+/// Text that is not a square in algebraic notation
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseSquareError(pub String);
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}' is not a square", self.0)
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(name: &str) -> Result<Square, ParseSquareError> {
+        let error = || ParseSquareError(name.to_string());
+        let mut chars = name.chars();
+        let file = chars.next().ok_or_else(error)?;
+        let rank = chars
+            .next()
+            .and_then(|c| c.to_digit(10))
+            .ok_or_else(error)?;
+        let last_file = (b'a' + MAX_FILES - 1) as char;
+        if chars.next().is_some()
+            || !('a'..=last_file).contains(&file)
+            || !(1..=RANKS as u32).contains(&rank)
+        {
+            return Err(error());
+        }
+        Ok(Square {
+            x: file as u8 - b'a',
+            y: RANKS - rank as u8,
+        })
+    }
+}
+
+impl Square {
+    pub fn is_white(&self) -> bool {
+        (self.x + self.y) % 2 == 1
+    }
+}
+
+impl Square {
+    /// Iterates over every square of a board with that many files
+    pub fn all(files: u8) -> impl Iterator<Item = Square> {
+        (0..RANKS).flat_map(move |y| (0..files).map(move |x| Square { x, y }))
+    }
+
+    /// The position of the square in a bitboard, counting along the ranks
+    /// from (0, 0). Every rank takes `MAX_FILES` bits, whatever the width of
+    /// the board.
+    pub fn index(&self) -> usize {
+        self.y as usize * MAX_FILES as usize + self.x as usize
+    }
+
+    pub fn from_index(index: usize) -> Square {
+        Square {
+            x: (index % MAX_FILES as usize) as u8,
+            y: (index / MAX_FILES as usize) as u8,
+        }
+    }
+
+    pub fn is_inbetween(&self, square1: Square, square2: Square) -> bool {
+        let min_x = min(square1.x, square2.x);
+        let max_x = max(square1.x, square2.x);
+        let min_y = min(square1.y, square2.y);
+        let max_y = max(square1.y, square2.y);
+        (square1.is_on_same_rank(square2)
+            && self.is_on_same_rank(square1)
+            && (min_x < self.x && self.x < max_x))
+            || (square1.is_on_same_file(square2)
+                && self.is_on_same_file(square1)
+                && (min_y < self.y && self.y < max_y))
+            || (square1.is_diagonal_to(square2)
+                && self.is_diagonal_to(square1)
+                && (min_x < self.x && self.x < max_x)
+                && (min_y < self.y && self.y < max_y))
+    }
+
+    // https://en.wikipedia.org/wiki/Chebyshev_distance
+    pub fn chebyshev_distance_to(&self, square: Square) -> u8 {
+        max(self.rank_distance_to(square), self.file_distance_to(square)) as u8
+    }
+
+    pub fn is_orthogonal_to(&self, square: Square) -> bool {
+        self.is_on_same_rank(square) || self.is_on_same_file(square)
+    }
+
+    pub fn is_diagonal_to(&self, square: Square) -> bool {
+        self.rank_distance_to(square) == self.file_distance_to(square)
+    }
+
+    pub fn is_on_same_rank(&self, square: Square) -> bool {
+        self.rank_distance_to(square) == 0
+    }
+
+    pub fn is_on_same_file(&self, square: Square) -> bool {
+        self.file_distance_to(square) == 0
+    }
+
+    pub fn rank_distance_to(&self, square: Square) -> u8 {
+        (square.y as i8 - self.y as i8).abs() as u8
+    }
+
+    pub fn file_distance_to(&self, square: Square) -> u8 {
+        (square.x as i8 - self.x as i8).abs() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::square::*;
+
+    #[test]
+    fn test_notation() {
+        struct Test {
+            name: &'static str,
+            square: Square,
+        }
+        let tests = vec![
+            Test {
+                name: "a8",
+                square: Square { x: 0, y: 0 },
+            },
+            Test {
+                name: "h1",
+                square: Square { x: 7, y: 7 },
+            },
+            Test {
+                name: "e4",
+                square: Square { x: 4, y: 4 },
+            },
+        ];
+        for test in tests {
+            assert_eq!(test.square.to_string(), test.name);
+            assert_eq!(test.name.parse(), Ok(test.square));
+        }
+        // The files go up to p, for the widest boards
+        assert_eq!("j1".parse(), Ok(Square { x: 9, y: 7 }));
+        for name in ["", "e", "e0", "e9", "q4", "E4", "e44"] {
+            assert_eq!(
+                name.parse::<Square>(),
+                Err(ParseSquareError(name.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_inbetween() {
+        struct Test {
+            square: Square,
+            square1: Square,
+            square2: Square,
+            result: bool,
+        }
+        let tests = vec![
+            // check all flanking variations
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 0, y: 1 },
+                square2: Square { x: 2, y: 1 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 0, y: 2 },
+                square2: Square { x: 2, y: 0 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 1, y: 2 },
+                square2: Square { x: 1, y: 0 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 2, y: 2 },
+                square2: Square { x: 0, y: 0 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 2, y: 1 },
+                square2: Square { x: 0, y: 1 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 2, y: 0 },
+                square2: Square { x: 0, y: 2 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 1, y: 0 },
+                square2: Square { x: 1, y: 2 },
+                result: true,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 2, y: 2 },
+                result: true,
+            },
+            // check if square is outside line
+            Test {
+                square: Square { x: 0, y: 5 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 0, y: 3 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 0, y: 2 },
+                square1: Square { x: 0, y: 7 },
+                square2: Square { x: 0, y: 4 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 5, y: 0 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 3, y: 0 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 2, y: 0 },
+                square1: Square { x: 7, y: 0 },
+                square2: Square { x: 4, y: 0 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 5, y: 5 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 3, y: 3 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 2, y: 2 },
+                square1: Square { x: 7, y: 7 },
+                square2: Square { x: 4, y: 4 },
+                result: false,
+            },
+            // check if square is the same as the line squares
+            Test {
+                square: Square { x: 0, y: 0 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 0, y: 4 },
+                result: false,
+            },
+            // check when square is not in line, but in between ranks/file
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 0, y: 3 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 0, y: 0 },
+                square2: Square { x: 3, y: 0 },
+                result: false,
+            },
+            Test {
+                square: Square { x: 1, y: 1 },
+                square1: Square { x: 1, y: 2 },
+                square2: Square { x: 3, y: 3 },
+                result: false,
+            },
+        ];
+        for test in tests {
+            assert_eq!(
+                test.square.is_inbetween(test.square1, test.square2),
+                test.result
+            )
+        }
+    }
+}
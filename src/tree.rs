@@ -0,0 +1,182 @@
+//! A game as a tree of moves: the main line, and the side lines that branch
+//! off it, each move with the comments and annotations attached to it.
+
+use crate::moves::*;
+use crate::position::*;
+
+/// Refers to a node of a `GameTree`
+pub type NodeId = usize;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Node {
+    /// The move leading to the node, which the root does not have
+    mv: Option<Move>,
+    parent: Option<NodeId>,
+    /// The moves played from the node, the main line first
+    children: Vec<NodeId>,
+    /// A comment before the move, which only the first move of a side line
+    /// can have
+    pub starting_comment: Option<String>,
+    /// A comment after the move, or about the whole game for the root
+    pub comment: Option<String>,
+    /// Numeric Annotation Glyphs, like 1 for a good move (`!`) or 2 for a
+    /// mistake (`?`)
+    pub nags: Vec<u8>,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, parent: Option<NodeId>) -> Node {
+        Node {
+            mv,
+            parent,
+            children: Vec::new(),
+            starting_comment: None,
+            comment: None,
+            nags: Vec::new(),
+        }
+    }
+
+    pub fn mv(&self) -> Option<Move> {
+        self.mv
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GameTree {
+    start: Position,
+    nodes: Vec<Node>,
+}
+
+impl GameTree {
+    /// The node before any move is made
+    pub const ROOT: NodeId = 0;
+
+    pub fn new(start: Position) -> GameTree {
+        GameTree {
+            start,
+            nodes: vec![Node::new(None, None)],
+        }
+    }
+
+    pub fn start(&self) -> &Position {
+        &self.start
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id]
+    }
+
+    /// The node for the move played after the parent node, added as the last
+    /// line from there unless the move was already in the tree
+    pub fn add_move(&mut self, parent: NodeId, mv: Move) -> NodeId {
+        if let Some(child) = self.child_with_move(parent, mv) {
+            return child;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node::new(Some(mv), Some(parent)));
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    pub fn child_with_move(&self, parent: NodeId, mv: Move) -> Option<NodeId> {
+        self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.nodes[*child].mv == Some(mv))
+    }
+
+    /// The moves from the start to the node
+    pub fn line_to(&self, id: NodeId) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = &self.nodes[id];
+        while let (Some(mv), Some(parent)) = (node.mv, node.parent) {
+            moves.push(mv);
+            node = &self.nodes[parent];
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// The last node of the main line, reached by always following the first
+    /// move from each node
+    pub fn main_line_end(&self) -> NodeId {
+        let mut id = GameTree::ROOT;
+        while let Some(child) = self.nodes[id].children.first() {
+            id = *child;
+        }
+        id
+    }
+
+    pub fn main_line(&self) -> Vec<Move> {
+        self.line_to(self.main_line_end())
+    }
+
+    /// Makes the line leading to the node the main line
+    pub fn promote(&mut self, id: NodeId) {
+        let mut child = id;
+        while let Some(parent) = self.nodes[child].parent {
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|c| *c == child).unwrap();
+            children[..=index].rotate_right(1);
+            child = parent;
+        }
+    }
+
+    /// The position after the moves leading to the node
+    pub fn position_at(&self, id: NodeId) -> Position {
+        let mut position = self.start;
+        for mv in self.line_to(id) {
+            position.make_move_unchecked(mv);
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::*;
+
+    #[test]
+    fn test_lines() {
+        let start = Position::default();
+        let mut tree = GameTree::new(start);
+        let play = |tree: &mut GameTree, parent, san| {
+            let mv = tree.position_at(parent).parse_san(san).unwrap();
+            tree.add_move(parent, mv)
+        };
+        let e4 = play(&mut tree, GameTree::ROOT, "e4");
+        let e5 = play(&mut tree, e4, "e5");
+        let c5 = play(&mut tree, e4, "c5");
+        let nf3 = play(&mut tree, c5, "Nf3");
+        assert_eq!(
+            play(&mut tree, e4, "c5"),
+            c5,
+            "moves already in the tree are reused"
+        );
+        assert_eq!(tree.node(e4).children(), &[e5, c5]);
+        assert_eq!(tree.main_line_end(), e5);
+        assert_eq!(tree.line_to(nf3).len(), 3);
+
+        tree.promote(nf3);
+        assert_eq!(tree.node(e4).children(), &[c5, e5]);
+        assert_eq!(tree.main_line_end(), nf3);
+        let mut position = start;
+        for mv in tree.main_line() {
+            position.make_move_unchecked(mv);
+        }
+        assert_eq!(tree.position_at(nf3), position);
+    }
+}
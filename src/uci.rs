@@ -0,0 +1,132 @@
+//! The long algebraic notation of the Universal Chess Interface, which engines
+//! use to exchange moves: the starting square, the target square and, for a
+//! promotion, the piece in lower case, like `e2e4` or `e7e8q`. Castling is
+//! written as the move of the king, like `e1g1`, or in Chess960 as the king
+//! taking its own rook, like `b1a1`; both are read. Crazyhouse drops are the
+//! piece in upper case, an `@` and the square, like `N@f3`. Writing moves this
+//! way is `Move::to_uci`, given the width of the board, or the `Display` of
+//! `Move` on eight files.
+
+use std::fmt;
+
+use crate::moves::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciError {
+    /// The text is not written like a move
+    Invalid(String),
+    /// No legal move matches the text
+    Illegal(String),
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciError::Invalid(uci) => write!(f, "'{}' is not a move", uci),
+            UciError::Illegal(uci) => write!(f, "{} is not a legal move", uci),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+impl Position {
+    /// Reads a move written in UCI notation
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, UciError> {
+        let invalid = || UciError::Invalid(uci.to_string());
+        if !uci.is_ascii() || uci.len() < 4 || uci.len() > 5 {
+            return Err(invalid());
+        }
+        if &uci[1..2] == "@" {
+            let kind = uci
+                .chars()
+                .next()
+                .and_then(PieceType::from_letter)
+                .ok_or_else(invalid)?;
+            let to: Square = uci[2..].parse().map_err(|_| invalid())?;
+            return self
+                .check_drop(kind, to)
+                .map_err(|_| UciError::Illegal(uci.to_string()));
+        }
+        let from: Square = uci[0..2].parse().map_err(|_| invalid())?;
+        let to: Square = uci[2..4].parse().map_err(|_| invalid())?;
+        let promotion = match uci[4..].chars().next() {
+            Some(c) if c.is_ascii_lowercase() => match PieceType::from_letter(c) {
+                Some(kind) if self.variant().promotion_pieces().contains(&kind) => Some(kind),
+                _ => return Err(invalid()),
+            },
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+        self.find_move(from, to, promotion)
+            .ok_or_else(|| UciError::Illegal(uci.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::*;
+    use crate::uci::*;
+
+    #[test]
+    fn test_uci() {
+        struct Test {
+            fen: &'static str,
+            uci: &'static str,
+            kind: MoveKind,
+        }
+        let tests = vec![
+            Test {
+                fen: START_FEN,
+                uci: "e2e4",
+                kind: MoveKind::DoublePawnPush,
+            },
+            Test {
+                fen: START_FEN,
+                uci: "g1f3",
+                kind: MoveKind::Normal,
+            },
+            Test {
+                fen: "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+                uci: "e1g1",
+                kind: MoveKind::Castling(CastlingSide::Kingside),
+            },
+            Test {
+                fen: "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+                uci: "e5d6",
+                kind: MoveKind::EnPassant,
+            },
+            Test {
+                fen: "8/1P5k/8/8/8/8/8/K7 w - - 0 1",
+                uci: "b7b8n",
+                kind: MoveKind::Promotion(PieceType::Knight),
+            },
+        ];
+        for test in tests {
+            let position = Position::from_fen(test.fen).unwrap();
+            let mv = position.parse_uci(test.uci).unwrap();
+            assert_eq!(mv.kind, test.kind);
+            assert_eq!(mv.to_string(), test.uci);
+        }
+
+        let position = Position::default();
+        assert_eq!(
+            position.parse_uci("e2e5"),
+            Err(UciError::Illegal("e2e5".to_string()))
+        );
+        for uci in ["e2", "e2e4e", "e2e4k", "e2e4Q", "q2e4"] {
+            assert_eq!(
+                position.parse_uci(uci),
+                Err(UciError::Invalid(uci.to_string()))
+            );
+        }
+        let position = Position::from_fen("8/1P5k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(
+            position.parse_uci("b7b8").is_err(),
+            "a promotion needs its piece"
+        );
+    }
+}
@@ -0,0 +1,653 @@
+use bevy::prelude::*;
+
+use chess::game::*;
+use chess::moves::*;
+use chess::piece::*;
+
+use crate::ai::*;
+use crate::board::*;
+use crate::fog::*;
+use crate::pieces::*;
+
+// Component to mark the overlay shown while a promotion is being picked
+struct PromotionPicker;
+
+// Component for the buttons of the promotion picker
+struct PromotionButton(PieceType);
+
+// Component to mark the banner announcing the result of the game
+struct ResultBanner;
+
+// Component for the message saying why a move was rejected, with how long
+// it is left up for
+struct RejectedMoveMessage(Timer);
+
+// Component for the button that claims a draw
+struct DrawClaimButton;
+
+// Component for the buttons that take back and replay moves
+enum HistoryButton {
+    Undo,
+    Redo,
+}
+
+// Component to mark the list of lines that follow the current position
+struct LineList;
+
+// Component for the buttons that step into one of those lines
+struct LineButton(Move);
+
+// Component for the screen hiding the board while it is handed over to the
+// next player in fog of war, which is clicked to take over
+struct HandOverScreen;
+
+struct ButtonMaterials {
+    normal: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+    overlay: Handle<ColorMaterial>,
+    screen: Handle<ColorMaterial>,
+}
+
+impl FromWorld for ButtonMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        ButtonMaterials {
+            normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+            hovered: materials.add(Color::rgb(0.8, 0.3, 0.3).into()),
+            overlay: materials.add(Color::rgba(0., 0., 0., 0.5).into()),
+            screen: materials.add(Color::rgb(0.05, 0.05, 0.1).into()),
+        }
+    }
+}
+
+fn init_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    fog: Option<Res<FogOfWar>>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    // Going back through the moves in fog of war would show a player the
+    // other's view
+    if fog.is_some() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    for (button, label, position) in [
+        (
+            HistoryButton::Undo,
+            "Undo",
+            Rect {
+                bottom: Val::Px(20.),
+                left: Val::Px(20.),
+                ..Default::default()
+            },
+        ),
+        (
+            HistoryButton::Redo,
+            "Redo",
+            Rect {
+                bottom: Val::Px(20.),
+                right: Val::Px(20.),
+                ..Default::default()
+            },
+        ),
+    ] {
+        commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(120.), Val::Px(50.)),
+                    position_type: PositionType::Absolute,
+                    position,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                material: materials.normal.clone(),
+                ..Default::default()
+            })
+            .insert(button)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        label,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+fn show_promotion_picker(
+    mut commands: Commands,
+    pending_promotion: Res<PendingPromotion>,
+    game: Res<Game>,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    picker_query: Query<Entity, With<PromotionPicker>>,
+) {
+    if !pending_promotion.is_changed() {
+        return;
+    }
+    for entity in picker_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if pending_promotion.squares.is_none() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let promotion_pieces = game.position().variant().promotion_pieces();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                // Capablanca chess has too many choices for one row
+                flex_wrap: FlexWrap::Wrap,
+                align_content: AlignContent::Center,
+                ..Default::default()
+            },
+            material: materials.overlay.clone(),
+            ..Default::default()
+        })
+        .insert(PromotionPicker)
+        .with_children(|parent| {
+            let choices = [
+                (PieceType::Queen, "Queen"),
+                (PieceType::Chancellor, "Chancellor"),
+                (PieceType::Archbishop, "Archbishop"),
+                (PieceType::Rook, "Rook"),
+                (PieceType::Bishop, "Bishop"),
+                (PieceType::Knight, "Knight"),
+                (PieceType::King, "King"),
+            ];
+            for (kind, label) in choices
+                .iter()
+                .filter(|(kind, _)| promotion_pieces.contains(kind))
+            {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(150.), Val::Px(65.)),
+                            margin: Rect::all(Val::Px(10.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(PromotionButton(*kind))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                *label,
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: 30.0,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+fn pick_promotion(
+    materials: Res<ButtonMaterials>,
+    mut events: EventWriter<Promote>,
+    mut query: Query<
+        (&Interaction, &PromotionButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, button, mut material) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => events.send(Promote { kind: button.0 }),
+            Interaction::Hovered => *material = materials.hovered.clone(),
+            Interaction::None => *material = materials.normal.clone(),
+        }
+    }
+}
+
+fn show_result_banner(
+    mut commands: Commands,
+    game: Res<Game>,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    banner_query: Query<Entity, With<ResultBanner>>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    for entity in banner_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let result = match game.result() {
+        Some(result) => result,
+        None => return,
+    };
+    let winner = match result.outcome {
+        Outcome::Win(PieceColor::White) => "White wins",
+        Outcome::Win(PieceColor::Black) => "Black wins",
+        Outcome::Draw => "Draw",
+    };
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(80.)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.overlay.clone(),
+            ..Default::default()
+        })
+        .insert(ResultBanner)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("{}! {} ({})", result.description(), winner, result.score()),
+                    TextStyle {
+                        font,
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn show_draw_claim(
+    mut commands: Commands,
+    game: Res<Game>,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    button_query: Query<Entity, With<DrawClaimButton>>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    for entity in button_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let reason = match game.claimable_draw() {
+        Some(reason) if !game.is_over() => reason,
+        _ => return,
+    };
+    let description = GameResult {
+        outcome: Outcome::Draw,
+        reason,
+    }
+    .description();
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(450.), Val::Px(50.)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(20.),
+                    left: Val::Px(275.),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(DrawClaimButton)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("Claim draw ({})", description.to_lowercase()),
+                    TextStyle {
+                        font,
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn claim_draw(
+    mut game: ResMut<Game>,
+    materials: Res<ButtonMaterials>,
+    mut query: Query<
+        (&Interaction, &DrawClaimButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, _button, mut material) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => game.claim_draw(),
+            Interaction::Hovered => *material = materials.hovered.clone(),
+            Interaction::None => *material = materials.normal.clone(),
+        }
+    }
+}
+
+fn use_history(
+    mut game: ResMut<Game>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    materials: Res<ButtonMaterials>,
+    computer: Option<Res<ComputerPlayer>>,
+    mut query: Query<
+        (&Interaction, &HistoryButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+) {
+    let computer_to_move = |game: &Game| {
+        computer.as_ref().map_or(false, |computer| {
+            computer.plays_against(game.position().side_to_move())
+        })
+    };
+    for (interaction, button, mut material) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                // A promotion being picked belongs to a move that is no longer next
+                pending_promotion.squares = None;
+                // Against the computer, its move goes along with the person's,
+                // as otherwise it would play again at once after an undo
+                let step = |game: &mut Game| match button {
+                    HistoryButton::Undo => game.undo(),
+                    HistoryButton::Redo => game.redo(),
+                };
+                if step(&mut game).is_some() && computer_to_move(&game) {
+                    step(&mut game);
+                }
+            }
+            Interaction::Hovered => *material = materials.hovered.clone(),
+            Interaction::None => *material = materials.normal.clone(),
+        }
+    }
+}
+
+/// Lists the moves that follow the current position when there is more than
+/// one, like the side lines of a loaded game or lines tried before, so that
+/// any of them can be stepped into
+fn show_lines(
+    mut commands: Commands,
+    game: Res<Game>,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    fog: Option<Res<FogOfWar>>,
+    list_query: Query<Entity, With<LineList>>,
+) {
+    // Stepping into another line is as good as taking moves back
+    if !game.is_changed() || fog.is_some() {
+        return;
+    }
+    for entity in list_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let lines = game.lines();
+    if lines.len() < 2 {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(80.),
+                    right: Val::Px(20.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.overlay.clone(),
+            ..Default::default()
+        })
+        .insert(LineList)
+        .with_children(|parent| {
+            for mv in lines {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(120.), Val::Px(40.)),
+                            margin: Rect::all(Val::Px(5.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(LineButton(mv))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                game.position().to_san(mv),
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: 25.0,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+fn pick_line(
+    mut game: ResMut<Game>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    materials: Res<ButtonMaterials>,
+    mut query: Query<(&Interaction, &LineButton, &mut Handle<ColorMaterial>), Changed<Interaction>>,
+) {
+    for (interaction, button, mut material) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                pending_promotion.squares = None;
+                game.make_move(button.0).ok();
+            }
+            Interaction::Hovered => *material = materials.hovered.clone(),
+            Interaction::None => *material = materials.normal.clone(),
+        }
+    }
+}
+
+fn show_rejected_move(
+    mut commands: Commands,
+    mut events: EventReader<RejectedMove>,
+    game: Res<Game>,
+    fog: Option<Res<FogOfWar>>,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    message_query: Query<Entity, With<RejectedMoveMessage>>,
+) {
+    let reason = events.iter().last().map(|event| event.0);
+    // A move that was made makes the message out of date
+    if reason.is_none() && !game.is_changed() {
+        return;
+    }
+    for entity in message_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let reason = match reason {
+        Some(reason) => reason,
+        None => return,
+    };
+
+    // In dark chess, why a move is illegal may be down to pieces the player
+    // cannot see
+    let message = if fog.is_some() && reason.depends_on_opponent() {
+        "Illegal move".to_string()
+    } else {
+        format!("Illegal move: {}", reason)
+    };
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(60.)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.overlay.clone(),
+            ..Default::default()
+        })
+        .insert(RejectedMoveMessage(Timer::from_seconds(2., false)))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    message,
+                    TextStyle {
+                        font,
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn show_hand_over(
+    mut commands: Commands,
+    game: Res<Game>,
+    fog: Option<Res<FogOfWar>>,
+    asset_server: Res<AssetServer>,
+    materials: Res<ButtonMaterials>,
+    screen_query: Query<Entity, With<HandOverScreen>>,
+) {
+    let fog = match fog {
+        Some(fog) if fog.is_changed() || game.is_changed() => fog,
+        _ => return,
+    };
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !fog.is_handing_over(&game) {
+        return;
+    }
+    let next = match game.position().side_to_move() {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    };
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    // The whole screen is a button, so that nothing under it can be clicked
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.screen.clone(),
+            ..Default::default()
+        })
+        .insert(HandOverScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("Pass the screen to {}, then click to play", next),
+                    TextStyle {
+                        font,
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn take_over(
+    game: Res<Game>,
+    fog: Option<ResMut<FogOfWar>>,
+    query: Query<&Interaction, (Changed<Interaction>, With<HandOverScreen>)>,
+) {
+    if let Some(mut fog) = fog {
+        if query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Clicked)
+        {
+            fog.viewer = Some(game.position().side_to_move());
+        }
+    }
+}
+
+fn expire_rejected_move(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut RejectedMoveMessage)>,
+) {
+    for (entity, mut message) in query.iter_mut() {
+        if message.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ButtonMaterials>()
+            .add_startup_system(init_ui.system())
+            .add_system(show_promotion_picker.system())
+            .add_system(pick_promotion.system())
+            .add_system(show_result_banner.system())
+            .add_system(show_draw_claim.system())
+            .add_system(claim_draw.system())
+            .add_system(use_history.system())
+            .add_system(show_lines.system())
+            .add_system(pick_line.system())
+            .add_system(show_rejected_move.system())
+            .add_system(expire_rejected_move.system())
+            .add_system(show_hand_over.system())
+            // Runs after the board has handled this frame's click, so the click
+            // that takes over the screen is not also taken as a move
+            .add_system_to_stage(CoreStage::PostUpdate, take_over.system());
+    }
+}
@@ -0,0 +1,396 @@
+//! Variants change the rules of the game: which moves are legal, and how a
+//! game can be won. A position keeps the variant it is played under, and the
+//! move generator and the end of game checks ask it where the rules differ.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitboard::*;
+use crate::fen::*;
+use crate::game::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Variant {
+    Standard,
+    /// Bringing the king to one of the four centre squares also wins
+    KingOfTheHill,
+    /// Giving check for the third time also wins
+    ThreeCheck,
+    /// Captures are compulsory, the king is an ordinary piece, and the side
+    /// that loses all its pieces or has no move left wins
+    Antichess,
+    /// Captured pieces can be dropped back onto the board by the capturer
+    Crazyhouse,
+    /// Played on ten files, with an archbishop and a chancellor added to each
+    /// side
+    Capablanca,
+}
+
+pub const VARIANTS: [Variant; 6] = [
+    Variant::Standard,
+    Variant::KingOfTheHill,
+    Variant::ThreeCheck,
+    Variant::Antichess,
+    Variant::Crazyhouse,
+    Variant::Capablanca,
+];
+
+/// A pawn may also promote to a king in Antichess
+const ANTICHESS_PROMOTION_PIECES: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::King,
+];
+
+/// A pawn may also promote to one of the fairy pieces in Capablanca chess
+const CAPABLANCA_PROMOTION_PIECES: [PieceType; 6] = [
+    PieceType::Queen,
+    PieceType::Chancellor,
+    PieceType::Archbishop,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// The hill of King of the Hill: d4, e4, d5 and e5
+fn centre() -> Bitboard {
+    let mut centre = Bitboard::EMPTY;
+    for x in 3..5 {
+        for y in 3..5 {
+            centre |= Bitboard::from_square(Square { x, y });
+        }
+    }
+    centre
+}
+
+// Written out, as `#[default]` on a variant needs a newer Rust than the
+// `rust-version` in Cargo.toml
+impl Default for Variant {
+    fn default() -> Self {
+        Variant::Standard
+    }
+}
+
+/// The name of the variant as written in the PGN `Variant` tag
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Variant::Standard => "Standard",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::ThreeCheck => "Three-check",
+            Variant::Antichess => "Antichess",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::Capablanca => "Capablanca",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Text that is not the name of a variant
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseVariantError(pub String);
+
+impl fmt::Display for ParseVariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}' is not a known variant", self.0)
+    }
+}
+
+impl std::error::Error for ParseVariantError {}
+
+/// Reads the name of a variant in any case, with spaces and dashes optional
+impl FromStr for Variant {
+    type Err = ParseVariantError;
+
+    fn from_str(name: &str) -> Result<Variant, ParseVariantError> {
+        let simplify = |name: &str| -> String {
+            name.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .map(|c| c.to_ascii_lowercase())
+                .collect()
+        };
+        VARIANTS
+            .iter()
+            .copied()
+            .find(|variant| simplify(&variant.to_string()) == simplify(name))
+            .ok_or_else(|| ParseVariantError(name.to_string()))
+    }
+}
+
+impl Variant {
+    /// The position games of the variant start from
+    pub fn start_position(self) -> Position {
+        let position = match self {
+            Variant::Capablanca => Position::from_fen(CAPABLANCA_FEN).unwrap(),
+            _ => Position::default(),
+        };
+        position.with_variant(self)
+    }
+
+    /// Whether the king may never be left in check, and may castle. Otherwise
+    /// it moves and is captured like any other piece.
+    pub fn has_royal_king(self) -> bool {
+        self != Variant::Antichess
+    }
+
+    /// Whether captured pieces go into a pocket to be dropped later
+    pub fn has_drops(self) -> bool {
+        self == Variant::Crazyhouse
+    }
+
+    /// Whether a side able to capture must do so
+    pub fn captures_compulsory(self) -> bool {
+        self == Variant::Antichess
+    }
+
+    /// The pieces a pawn can promote to
+    pub fn promotion_pieces(self) -> &'static [PieceType] {
+        match self {
+            Variant::Antichess => &ANTICHESS_PROMOTION_PIECES,
+            Variant::Capablanca => &CAPABLANCA_PROMOTION_PIECES,
+            _ => &PROMOTION_PIECES,
+        }
+    }
+
+    /// The win the last move brought about under the variant's own rules, on
+    /// top of checkmate
+    pub fn result(self, position: &Position) -> Option<GameResult> {
+        let mover = position.side_to_move().opponent();
+        let reason = match self {
+            Variant::KingOfTheHill
+                if !(position.pieces_of(PieceType::King, mover) & centre()).is_empty() =>
+            {
+                EndReason::KingOfTheHill
+            }
+            Variant::ThreeCheck if position.checks_given(mover) >= 3 => EndReason::ThreeChecks,
+            _ => return None,
+        };
+        Some(GameResult {
+            outcome: Outcome::Win(mover),
+            reason,
+        })
+    }
+
+    /// How the game ends when the side to move has no legal move
+    pub fn no_moves_result(self, position: &Position) -> GameResult {
+        let side_to_move = position.side_to_move();
+        if self == Variant::Antichess {
+            let reason = if position.pieces_of_color(side_to_move).is_empty() {
+                EndReason::AllPiecesLost
+            } else {
+                EndReason::Stalemate
+            };
+            return GameResult {
+                outcome: Outcome::Win(side_to_move),
+                reason,
+            };
+        }
+        if position.is_in_check() {
+            GameResult {
+                outcome: Outcome::Win(side_to_move.opponent()),
+                reason: EndReason::Checkmate,
+            }
+        } else {
+            GameResult {
+                outcome: Outcome::Draw,
+                reason: EndReason::Stalemate,
+            }
+        }
+    }
+
+    /// Whether neither side can win any more. Checkmate needs more than a
+    /// minor piece, but a check only needs a piece other than the king, and
+    /// a king can always walk to the centre. Nothing ever leaves the board in
+    /// Crazyhouse.
+    pub fn is_insufficient_material(self, position: &Position) -> bool {
+        match self {
+            Variant::Standard | Variant::Capablanca => position.is_insufficient_material(),
+            Variant::ThreeCheck => {
+                let kings = position.pieces_of(PieceType::King, PieceColor::White)
+                    | position.pieces_of(PieceType::King, PieceColor::Black);
+                position.occupied() == kings
+            }
+            Variant::KingOfTheHill | Variant::Antichess | Variant::Crazyhouse => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::moves::*;
+    use crate::variant::*;
+
+    fn play(game: &mut Game, uci: &str) {
+        let mv = game.position().parse_uci(uci).unwrap();
+        game.make_move(mv).unwrap();
+    }
+
+    #[test]
+    fn test_notation() {
+        for variant in VARIANTS {
+            assert_eq!(variant.to_string().parse(), Ok(variant));
+        }
+        assert_eq!("kingofthehill".parse(), Ok(Variant::KingOfTheHill));
+        assert_eq!("three check".parse(), Ok(Variant::ThreeCheck));
+        assert_eq!(
+            "Atomic".parse::<Variant>(),
+            Err(ParseVariantError("Atomic".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let position = Position::from_fen("4k3/8/8/8/8/4K3/8/8 w - - 0 1").unwrap();
+        let mut game = Game::new(position.with_variant(Variant::KingOfTheHill));
+        assert!(!game.is_over(), "bare kings are not a draw");
+        play(&mut game, "e3e4");
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                outcome: Outcome::Win(PieceColor::White),
+                reason: EndReason::KingOfTheHill,
+            })
+        );
+    }
+
+    #[test]
+    fn test_three_check() {
+        let mut game = Game::new(Position::default().with_variant(Variant::ThreeCheck));
+        for uci in ["e2e4", "e7e5", "f1c4", "b8c6", "c4f7"] {
+            play(&mut game, uci);
+        }
+        assert_eq!(game.position().checks_given(PieceColor::White), 1);
+        // The same squares with more checks given is a different position
+        assert_ne!(
+            game.position().hash(),
+            Position::from_fen(&game.position().to_fen())
+                .unwrap()
+                .with_variant(Variant::ThreeCheck)
+                .hash()
+        );
+        for uci in ["e8f7", "d1h5", "f7e7"] {
+            play(&mut game, uci);
+        }
+        assert_eq!(game.position().checks_given(PieceColor::White), 2);
+        play(&mut game, "h5e5");
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                outcome: Outcome::Win(PieceColor::White),
+                reason: EndReason::ThreeChecks,
+            })
+        );
+        game.undo();
+        assert_eq!(game.position().checks_given(PieceColor::White), 2);
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn test_antichess() {
+        let position = Position::default().with_variant(Variant::Antichess);
+        assert_eq!(position.to_fen(), START_FEN.replace("KQkq", "-"));
+        let mut game = Game::new(position);
+        play(&mut game, "e2e4");
+        play(&mut game, "d7d5");
+        // Taking is compulsory, so only the capture is legal
+        assert_eq!(game.position().legal_moves().len(), 1);
+        let square = |name: &str| name.parse::<Square>().unwrap();
+        assert_eq!(
+            game.position().check_move(square("g1"), square("f3"), None),
+            Err(IllegalMove::CaptureRequired)
+        );
+        play(&mut game, "e4d5");
+        play(&mut game, "d8d5");
+        play(&mut game, "d1h5");
+        // There is no check, and the king takes like any other piece
+        play(&mut game, "d5d2");
+        assert!(!game.position().is_in_check());
+        play(&mut game, "e1d2");
+
+        // Pawns may promote to a king, and the side left without pieces wins
+        let position =
+            Position::from_variant_fen("8/3P4/8/8/8/8/8/7q w - - 0 1", Variant::Antichess).unwrap();
+        assert_eq!(position.legal_moves().len(), 5);
+        let mut game = Game::new(position);
+        play(&mut game, "d7d8k");
+        play(&mut game, "h1a8");
+        play(&mut game, "d8c8");
+        play(&mut game, "a8c8");
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                outcome: Outcome::Win(PieceColor::White),
+                reason: EndReason::AllPiecesLost,
+            })
+        );
+    }
+
+    #[test]
+    fn test_capablanca() {
+        let position = Variant::Capablanca.start_position();
+        assert_eq!(position.files(), 10);
+        assert_eq!(position.to_fen(), CAPABLANCA_FEN);
+        assert!(!position.has_chess960_castling());
+        assert_eq!(position.legal_moves().len(), 28);
+        let square = |name: &str| name.parse::<Square>().unwrap();
+        assert_eq!(
+            Position::default().check_move(square("h1"), square("i1"), None),
+            Err(IllegalMove::OffBoard)
+        );
+
+        // The fairy pieces leap like knights as well as sliding
+        let mut game = Game::new(position);
+        for san in ["Cg3", "Ci6", "Ad3", "Ab6"] {
+            let mv = game.position().parse_san(san).unwrap();
+            game.make_move(mv).unwrap();
+        }
+        assert_eq!(
+            game.position().to_fen(),
+            "rn1bqkb1nr/pppppppppp/1a6c1/10/10/3A2C3/PPPPPPPPPP/RN1BQKB1NR w KQkq - 4 3"
+        );
+
+        // The king castles to the i-file on the kingside, and to the c-file on
+        // the queenside
+        let position =
+            Position::from_fen("r4k3r/pppppppppp/10/10/10/10/PPPPPPPPPP/R4K3R w KQkq - 0 1")
+                .unwrap()
+                .with_variant(Variant::Capablanca);
+        for (san, uci, fen) in [
+            (
+                "O-O",
+                "f1i1",
+                "r4k3r/pppppppppp/10/10/10/10/PPPPPPPPPP/R6RK1 b kq - 1 1",
+            ),
+            (
+                "O-O-O",
+                "f1c1",
+                "r4k3r/pppppppppp/10/10/10/10/PPPPPPPPPP/2KR5R b kq - 1 1",
+            ),
+        ] {
+            let mv = position.parse_san(san).unwrap();
+            assert_eq!(mv.to_uci(position.files()), uci);
+            assert_eq!(position.parse_uci(uci), Ok(mv));
+            let mut next = position;
+            next.make_move(mv).unwrap();
+            assert_eq!(next.to_fen(), fen);
+        }
+        assert_eq!(
+            position.parse_uci("f1i1").ok(),
+            position.parse_san("O-O").ok()
+        );
+
+        // Pawns may promote to the fairy pieces too
+        let position = Position::from_fen("4k5/1P8/10/10/10/10/10/4K5 w - - 0 1")
+            .unwrap()
+            .with_variant(Variant::Capablanca);
+        assert_eq!(position.legal_moves().len(), 6 + 5);
+        let mv = position.parse_san("b8=C+").unwrap();
+        assert_eq!(mv.kind, MoveKind::Promotion(PieceType::Chancellor));
+        assert_eq!(position.to_san(mv), "b8=C+");
+    }
+}
@@ -0,0 +1,157 @@
+//! Zobrist hashing gives every feature of a position (a piece on a square, the
+//! side to move, each castling right, the en passant file, the checks given in
+//! Three-check, the pieces in a Crazyhouse pocket) a random 64-bit key. A
+//! position's hash is the XOR of the keys of its features, so a move updates
+//! it by XORing out what changed and XORing in what replaced it.
+
+use std::cmp::min;
+
+use crate::piece::*;
+use crate::position::*;
+use crate::square::*;
+
+const KINDS: usize = PIECE_TYPES.len();
+
+struct Keys {
+    pieces: [[[u64; SQUARES]; KINDS]; 2],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant: [u64; MAX_FILES as usize],
+    /// For each side having given one, two or three checks in Three-check
+    checks: [[u64; 3]; 2],
+    /// For each piece a side holds in its pocket in Crazyhouse, by how many of
+    /// that kind it holds
+    pocket: [[[u64; 16]; KINDS]; 2],
+}
+
+/// The SplitMix64 generator: returns the next state and its output
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+/// The keys are generated at compile time from a fixed seed, so hashes stay
+/// the same from one run to the next
+const fn generate_keys() -> Keys {
+    let mut state = 0x0123_4567_89ab_cdef;
+    let mut keys = Keys {
+        pieces: [[[0; SQUARES]; KINDS]; 2],
+        black_to_move: 0,
+        castling: [0; 4],
+        en_passant: [0; MAX_FILES as usize],
+        checks: [[0; 3]; 2],
+        pocket: [[[0; 16]; KINDS]; 2],
+    };
+    let mut color = 0;
+    while color < 2 {
+        let mut kind = 0;
+        while kind < KINDS {
+            let mut square = 0;
+            while square < SQUARES {
+                let (next, key) = split_mix(state);
+                state = next;
+                keys.pieces[color][kind][square] = key;
+                square += 1;
+            }
+            kind += 1;
+        }
+        color += 1;
+    }
+    let (next, key) = split_mix(state);
+    state = next;
+    keys.black_to_move = key;
+    let mut i = 0;
+    while i < 4 {
+        let (next, key) = split_mix(state);
+        state = next;
+        keys.castling[i] = key;
+        i += 1;
+    }
+    let mut file = 0;
+    while file < MAX_FILES as usize {
+        let (next, key) = split_mix(state);
+        state = next;
+        keys.en_passant[file] = key;
+        file += 1;
+    }
+    let mut color = 0;
+    while color < 2 {
+        let mut count = 0;
+        while count < 3 {
+            let (next, key) = split_mix(state);
+            state = next;
+            keys.checks[color][count] = key;
+            count += 1;
+        }
+        color += 1;
+    }
+    let mut color = 0;
+    while color < 2 {
+        let mut kind = 0;
+        while kind < KINDS {
+            let mut count = 0;
+            while count < 16 {
+                let (next, key) = split_mix(state);
+                state = next;
+                keys.pocket[color][kind][count] = key;
+                count += 1;
+            }
+            kind += 1;
+        }
+        color += 1;
+    }
+    keys
+}
+
+const KEYS: Keys = generate_keys();
+
+pub fn piece_key(piece: Piece) -> u64 {
+    KEYS.pieces[piece.color as usize][piece.kind as usize][piece.square.index()]
+}
+
+/// Included in the hash when Black is to move
+pub fn black_to_move_key() -> u64 {
+    KEYS.black_to_move
+}
+
+/// The combined key of every castling right still held
+pub fn castling_key(rights: CastlingRights) -> u64 {
+    let held = [
+        rights.white_kingside,
+        rights.white_queenside,
+        rights.black_kingside,
+        rights.black_queenside,
+    ];
+    let mut key = 0;
+    for (i, held) in held.iter().enumerate() {
+        if held.is_some() {
+            key ^= KEYS.castling[i];
+        }
+    }
+    key
+}
+
+pub fn en_passant_key(file: u8) -> u64 {
+    KEYS.en_passant[file as usize]
+}
+
+/// Included in the hash for the `count`th piece of a kind in a side's pocket,
+/// so the hash changes by one key as a piece goes in or comes out
+pub fn pocket_key(color: PieceColor, kind: PieceType, count: u8) -> u64 {
+    match count {
+        0 => 0,
+        count => KEYS.pocket[color as usize][kind as usize][(count as usize - 1) % 16],
+    }
+}
+
+/// Included in the hash when a side has given some checks, which only
+/// Three-check counts
+pub fn checks_key(color: PieceColor, count: u8) -> u64 {
+    match count {
+        0 => 0,
+        count => KEYS.checks[color as usize][min(count, 3) as usize - 1],
+    }
+}